@@ -0,0 +1,72 @@
+use std::{collections::HashSet, env, fs, path::Path};
+
+#[path = "src/pdk/prompt_file.rs"]
+mod prompt_file;
+#[path = "src/pdk/template.rs"]
+mod template;
+
+/// Embeds every Markdown file in `prompts/` so the pdk can serve them as prompts, failing
+/// the build if one of them would be rejected when the plugin registers it.
+fn main() {
+    let dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("prompts");
+    // Watching a missing path would make Cargo rerun this script on every build.
+    if dir.exists() {
+        println!("cargo:rerun-if-changed={}", dir.display());
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/pdk/prompt_file.rs");
+    println!("cargo:rerun-if-changed=src/pdk/template.rs");
+
+    let mut files: Vec<_> = fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "md"))
+        .collect();
+    files.sort();
+
+    let mut names = HashSet::new();
+    let mut out = String::from("&[\n");
+    for path in files {
+        let name = path.file_name().unwrap().to_string_lossy();
+        let source = fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("cannot read prompt file '{name}': {e}"));
+        let prompt = check_prompt(&source, name.strip_suffix(".md").unwrap_or(&name))
+            .unwrap_or_else(|e| panic!("invalid prompt file '{name}': {e:#}"));
+        if !names.insert(prompt.clone()) {
+            panic!("invalid prompt file '{name}': prompt '{prompt}' is already defined");
+        }
+        out.push_str(&format!("    ({name:?}, include_str!({path:?})),\n"));
+    }
+    out.push_str("]\n");
+
+    fs::write(
+        Path::new(&env::var("OUT_DIR").unwrap()).join("prompts.rs"),
+        out,
+    )
+    .unwrap();
+}
+
+/// Checks a prompt file the way the pdk registers it, returning the prompt's name.
+fn check_prompt(source: &str, default_name: &str) -> anyhow::Result<String> {
+    let (front_matter, body, offset) = prompt_file::parse_front_matter(source)?;
+    let declared: Vec<&str> = front_matter
+        .arguments
+        .iter()
+        .map(|argument| argument.name.as_str())
+        .collect();
+    for (_, text) in prompt_file::parse_messages(body, offset)? {
+        let template = template::Template::parse(&text)?;
+        if let Some(name) = template
+            .variables()
+            .into_iter()
+            .find(|name| !declared.contains(&name.as_str()))
+        {
+            anyhow::bail!("template uses undeclared argument '{name}'");
+        }
+    }
+    Ok(front_matter
+        .name
+        .unwrap_or_else(|| default_name.to_string()))
+}
@@ -0,0 +1,135 @@
+#![allow(unused)]
+use super::types::*;
+use anyhow::Result;
+use std::{collections::VecDeque, fmt, sync::Mutex};
+
+/// Maximum number of cancelled requests remembered at a time.
+const MAX_CANCELLED: usize = 256;
+
+/// Requests the client cancelled, oldest first.
+static CANCELLED: Mutex<VecDeque<CancelledNotificationParam>> = Mutex::new(VecDeque::new());
+
+/// Records that the client cancelled a request.
+pub(crate) fn record(notification: CancelledNotificationParam) {
+    let mut cancelled = CANCELLED.lock().unwrap_or_else(|e| e.into_inner());
+    cancelled.retain(|c| c.request_id != notification.request_id);
+    if cancelled.len() >= MAX_CANCELLED {
+        cancelled.pop_front();
+    }
+    cancelled.push_back(notification);
+}
+
+/// Fails with [`Cancelled`] if the client cancelled the request with `context` before its
+/// call started, so that its handler does not run at all.
+///
+/// # Host contract
+///
+/// A plugin instance runs one call at a time and keeps the recorded cancellations in its
+/// own memory, so only the `on_cancelled` calls made to the same instance before the
+/// request's own call started are seen here, e.g. for a request that waited for a busy
+/// instance. Extism does not enter an instance that is running a call, so a cancellation
+/// sent while the handler runs is only recorded after it has returned, and a running
+/// handler cannot be interrupted.
+pub(crate) fn reject_cancelled(context: &PluginRequestContext) -> Result<()> {
+    let mut cancelled = CANCELLED.lock().unwrap_or_else(|e| e.into_inner());
+    match cancelled
+        .iter()
+        .position(|c| c.request_id == context.id)
+        .and_then(|index| cancelled.remove(index))
+    {
+        Some(notification) => Err(Cancelled {
+            reason: notification.reason,
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// The error of a request the client cancelled before its handler ran.
+#[derive(Debug, Clone)]
+pub struct Cancelled {
+    /// The reason the client gave, if any
+    pub reason: Option<String>,
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the request was cancelled")?;
+        if let Some(reason) = &self.reason {
+            write!(f, ": {reason}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The statics are shared by every test, so tests take turns.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn context(id: &str) -> PluginRequestContext {
+        PluginRequestContext {
+            id: PluginRequestId::String(id.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn cancel(id: &str, reason: Option<&str>) {
+        record(CancelledNotificationParam {
+            reason: reason.map(String::from),
+            request_id: PluginRequestId::String(id.to_string()),
+        });
+    }
+
+    fn is_recorded(id: &str) -> bool {
+        let id = PluginRequestId::String(id.to_string());
+        CANCELLED.lock().unwrap().iter().any(|c| c.request_id == id)
+    }
+
+    #[test]
+    fn requests_cancelled_before_they_start_are_rejected_once() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        cancel("other", None);
+        reject_cancelled(&context("queued")).unwrap();
+
+        cancel("queued", Some("user pressed stop"));
+        let error = reject_cancelled(&context("queued")).unwrap_err();
+        let cancelled = error.downcast_ref::<Cancelled>().unwrap();
+        assert_eq!(cancelled.reason.as_deref(), Some("user pressed stop"));
+        assert_eq!(
+            error.to_string(),
+            "the request was cancelled: user pressed stop"
+        );
+        assert!(!is_recorded("queued"));
+        assert!(is_recorded("other"));
+        reject_cancelled(&context("queued")).unwrap();
+
+        let error = reject_cancelled(&context("other")).unwrap_err();
+        assert_eq!(error.to_string(), "the request was cancelled");
+        assert!(!is_recorded("other"));
+    }
+
+    #[test]
+    fn only_the_latest_cancellations_are_kept() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        cancel("dup", Some("first"));
+        cancel("dup", Some("second"));
+        let reason = reject_cancelled(&context("dup")).unwrap_err();
+        assert_eq!(reason.to_string(), "the request was cancelled: second");
+
+        cancel("dup", None);
+        for i in 0..MAX_CANCELLED {
+            cancel(&format!("bulk-{i}"), None);
+        }
+        assert_eq!(CANCELLED.lock().unwrap().len(), MAX_CANCELLED);
+        assert!(!is_recorded("dup"));
+        assert!(is_recorded("bulk-0"));
+        assert!(is_recorded(&format!("bulk-{}", MAX_CANCELLED - 1)));
+
+        CANCELLED.lock().unwrap().clear();
+    }
+}
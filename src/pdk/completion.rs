@@ -0,0 +1,338 @@
+#![allow(unused)]
+use super::{error::McpError, prompts, resources, types::*};
+use anyhow::{Result, anyhow};
+use schemars::{JsonSchema, schema_for};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Arc,
+};
+
+/// Maximum number of values a completion result may carry.
+pub const MAX_COMPLETION_VALUES: usize = 100;
+
+type Provider = Arc<dyn Fn(&str, &HashMap<String, String>) -> Result<Vec<String>> + Send + Sync>;
+
+/// A source of completion values for one prompt argument or URI-template variable.
+///
+/// Whatever the source, the candidates are matched against the value being typed and
+/// ranked: exact matches first, then prefix matches (case-sensitive before
+/// case-insensitive), then matches at the start of a word, then substrings, then fuzzy
+/// matches whose characters appear in order, tightest first. Within a rank the provider's
+/// order is kept. An empty value matches every candidate.
+#[derive(Clone)]
+pub struct Completion {
+    provider: Provider,
+}
+
+impl fmt::Debug for Completion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Completion").finish_non_exhaustive()
+    }
+}
+
+impl Completion {
+    /// Completes from a fixed list of values.
+    pub fn values<S: Into<String>>(values: impl IntoIterator<Item = S>) -> Self {
+        let values: Vec<String> = values.into_iter().map(Into::into).collect();
+        Completion::with(move |_, _| Ok(values.clone()))
+    }
+
+    /// Completes from the string values of a type whose JSON Schema is an enum, such as a
+    /// Rust enum of unit variants deriving `JsonSchema`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the schema of `T` lists no string values.
+    pub fn from_enum<T: JsonSchema>() -> Self {
+        let schema = schema_for!(T);
+        let mut values = Vec::new();
+        if let Some(Value::Array(variants)) = schema.get("enum") {
+            values.extend(variants.iter().filter_map(Value::as_str).map(String::from));
+        }
+        for key in ["oneOf", "anyOf"] {
+            if let Some(Value::Array(variants)) = schema.get(key) {
+                for variant in variants {
+                    if let Some(Value::String(value)) = variant.get("const") {
+                        values.push(value.clone());
+                    }
+                    if let Some(Value::Array(consts)) = variant.get("enum") {
+                        values.extend(consts.iter().filter_map(Value::as_str).map(String::from));
+                    }
+                }
+            }
+        }
+        if values.is_empty() {
+            panic!(
+                "cannot complete from '{}': its schema has no string enum values",
+                std::any::type_name::<T>()
+            );
+        }
+        Completion::values(values)
+    }
+
+    /// Completes from the candidates returned by `provider`.
+    ///
+    /// The provider receives the value being typed and the arguments the client has already
+    /// resolved (`CompleteRequestParamContext.arguments`), so candidates can depend on
+    /// earlier choices. It may pre-filter on the value, but need not.
+    pub fn with<F>(provider: F) -> Self
+    where
+        F: Fn(&str, &HashMap<String, String>) -> Result<Vec<String>> + Send + Sync + 'static,
+    {
+        Completion {
+            provider: Arc::new(provider),
+        }
+    }
+
+    /// Returns the ranked candidates matching `value`, at most [`MAX_COMPLETION_VALUES`].
+    pub fn complete(
+        &self,
+        value: &str,
+        arguments: &HashMap<String, String>,
+    ) -> Result<CompleteResultCompletion> {
+        let candidates = (self.provider)(value, arguments)?;
+
+        let mut seen = HashSet::new();
+        let mut ranked: Vec<((u8, usize), usize, String)> = candidates
+            .into_iter()
+            .filter(|candidate| seen.insert(candidate.clone()))
+            .enumerate()
+            .filter_map(|(i, candidate)| Some((rank(value, &candidate)?, i, candidate)))
+            .collect();
+        ranked.sort_by_key(|(rank, i, _)| (*rank, *i));
+
+        let total = ranked.len();
+        ranked.truncate(MAX_COMPLETION_VALUES);
+        Ok(CompleteResultCompletion {
+            has_more: Some(total > MAX_COMPLETION_VALUES),
+            total: Some(total as i64),
+            values: ranked.into_iter().map(|(_, _, value)| value).collect(),
+        })
+    }
+}
+
+/// Ranks `candidate` against the typed `value`; lower is better and `None` is no match.
+fn rank(value: &str, candidate: &str) -> Option<(u8, usize)> {
+    if value.is_empty() {
+        return Some((0, 0));
+    }
+    if candidate == value {
+        return Some((0, 0));
+    }
+    if candidate.starts_with(value) {
+        return Some((1, 0));
+    }
+
+    let value = value.to_lowercase();
+    let candidate = candidate.to_lowercase();
+    if candidate.starts_with(&value) {
+        return Some((2, 0));
+    }
+    let word_start = candidate
+        .match_indices(&value)
+        .any(|(i, _)| !candidate[..i].ends_with(|c: char| c.is_alphanumeric()));
+    if word_start {
+        return Some((3, 0));
+    }
+    if let Some(i) = candidate.find(&value) {
+        return Some((4, i));
+    }
+
+    // Fuzzy: every character of the value in order; rank by the span they cover.
+    let mut chars = candidate.char_indices();
+    let mut first = None;
+    let mut last = 0;
+    for wanted in value.chars() {
+        let (i, _) = chars.find(|&(_, c)| c == wanted)?;
+        first.get_or_insert(i);
+        last = i;
+    }
+    Some((5, last - first.unwrap_or(0)))
+}
+
+/// Answers a completion request from the prompt or resource template registry.
+///
+/// Fails if the reference names an unknown prompt or resource template; arguments and
+/// variables without a registered [`Completion`] complete to nothing.
+pub(crate) fn complete(input: CompleteRequest) -> Result<CompleteResult> {
+    let request = input.request;
+    let arguments = request
+        .context
+        .and_then(|context| context.arguments)
+        .unwrap_or_default();
+
+    let completion = match &request.r#ref {
+        Reference::Prompt(reference) => {
+            prompts::registry().completion(&reference.name, &request.argument.name)?
+        }
+        Reference::ResourceTemplate(reference) => {
+            resources::registry().completion(&reference.uri, &request.argument.name)?
+        }
+        Reference::Empty(_) => {
+            return Err(McpError::invalid_params("completion request has no reference").into());
+        }
+    };
+
+    Ok(CompleteResult {
+        completion: match completion {
+            Some(completion) => completion.complete(&request.argument.value, &arguments)?,
+            None => CompleteResultCompletion::default(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    fn complete_values(completion: &Completion, value: &str) -> Vec<String> {
+        completion.complete(value, &HashMap::new()).unwrap().values
+    }
+
+    #[test]
+    fn candidates_are_ranked_by_how_well_they_match() {
+        let completion = Completion::values([
+            "s.o.r.t",
+            "resort",
+            "by_sort",
+            "Sorted",
+            "sorted",
+            "sortable",
+            "s-ort",
+            "sort",
+            "unrelated",
+        ]);
+        assert_eq!(
+            complete_values(&completion, "sort"),
+            [
+                "sort", "sorted", "sortable", "Sorted", "by_sort", "resort", "s-ort", "s.o.r.t"
+            ]
+        );
+    }
+
+    #[test]
+    fn an_empty_value_keeps_the_provider_order() {
+        let completion = Completion::values(["b", "a", "c"]);
+        assert_eq!(complete_values(&completion, ""), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn duplicate_candidates_are_dropped() {
+        let completion = Completion::values(["red", "green", "red", "blue", "green"]);
+        let result = completion.complete("", &HashMap::new()).unwrap();
+        assert_eq!(result.values, ["red", "green", "blue"]);
+        assert_eq!(result.total, Some(3));
+        assert_eq!(result.has_more, Some(false));
+    }
+
+    #[test]
+    fn results_are_capped_with_the_total() {
+        let completion = Completion::values((0..150).map(|i| format!("v{i}")));
+        let result = completion.complete("", &HashMap::new()).unwrap();
+        assert_eq!(result.values.len(), MAX_COMPLETION_VALUES);
+        assert_eq!(result.values.first().map(String::as_str), Some("v0"));
+        assert_eq!(result.total, Some(150));
+        assert_eq!(result.has_more, Some(true));
+
+        let completion = Completion::values((0..MAX_COMPLETION_VALUES).map(|i| format!("v{i}")));
+        let result = completion.complete("", &HashMap::new()).unwrap();
+        assert_eq!(result.values.len(), MAX_COMPLETION_VALUES);
+        assert_eq!(result.has_more, Some(false));
+    }
+
+    #[test]
+    fn providers_see_the_value_and_resolved_arguments() {
+        let completion = Completion::with(|value, arguments| {
+            Ok(vec![format!("{}/{value}", arguments["owner"])])
+        });
+        let arguments = HashMap::from([("owner".to_string(), "octo".to_string())]);
+        let result = completion.complete("hel", &arguments).unwrap();
+        assert_eq!(result.values, ["octo/hel"]);
+
+        let failing = Completion::with(|_, _| Err(anyhow!("backend unavailable")));
+        let error = failing.complete("", &HashMap::new()).unwrap_err();
+        assert_eq!(error.to_string(), "backend unavailable");
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    #[serde(rename_all = "lowercase")]
+    enum Unit {
+        Celsius,
+        Fahrenheit,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    enum Documented {
+        /// The first one
+        First,
+        /// The second one
+        Second,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    struct NotAnEnum {
+        value: String,
+    }
+
+    #[test]
+    fn from_enum_completes_the_variant_names() {
+        assert_eq!(
+            complete_values(&Completion::from_enum::<Unit>(), ""),
+            ["celsius", "fahrenheit"]
+        );
+        assert_eq!(
+            complete_values(&Completion::from_enum::<Documented>(), "sec"),
+            ["Second"]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "its schema has no string enum values")]
+    fn from_enum_panics_without_enum_values() {
+        Completion::from_enum::<NotAnEnum>();
+    }
+
+    fn request(r#ref: Reference) -> CompleteRequest {
+        CompleteRequest {
+            context: PluginRequestContext::default(),
+            request: CompleteRequestParam {
+                argument: CompleteRequestParamArgument {
+                    name: "city".to_string(),
+                    value: "Par".to_string(),
+                },
+                context: None,
+                r#ref,
+            },
+        }
+    }
+
+    fn invalid_params(r#ref: Reference) -> McpError {
+        let error = complete(request(r#ref)).unwrap_err();
+        let error = error.downcast_ref::<McpError>().unwrap().clone();
+        assert_eq!(error.code, McpError::INVALID_PARAMS);
+        error
+    }
+
+    #[test]
+    fn unknown_references_are_invalid_params() {
+        let error = invalid_params(Reference::Prompt(PromptReference {
+            name: "weather".to_string(),
+            title: None,
+        }));
+        assert_eq!(error.message, "unknown prompt 'weather'");
+
+        let error = invalid_params(Reference::ResourceTemplate(ResourceTemplateReference {
+            uri: "weather://{city}".to_string(),
+        }));
+        assert_eq!(
+            error.message,
+            "unknown resource template 'weather://{city}'"
+        );
+
+        let error = invalid_params(Reference::Empty(Empty::default()));
+        assert_eq!(error.message, "completion request has no reference");
+    }
+}
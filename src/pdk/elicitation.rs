@@ -0,0 +1,432 @@
+#![allow(unused)]
+use super::{imports, types::*};
+use anyhow::{Context, Result, anyhow};
+use schemars::{JsonSchema, generate::SchemaSettings};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+
+/// A type that can be asked for with an elicitation form.
+///
+/// Implemented for every type deriving `Deserialize` and `JsonSchema` whose schema is an
+/// object with only string, number, integer, boolean or string enum fields. Doc comments
+/// become the fields' descriptions (and titles, when set with `#[schemars(title = ...)]`),
+/// documented enum variants get their description as display name, and fields that are
+/// `Option`s or have a default are not required.
+///
+/// ```ignore
+/// #[derive(Deserialize, JsonSchema)]
+/// struct Issue {
+///     /// One-line summary
+///     title: String,
+///     priority: Priority,
+///     /// Who should work on it, if anyone
+///     assignee: Option<String>,
+/// }
+///
+/// if let Some(issue) = elicit::<Issue>("Describe the issue to create.")? {
+///     create_issue(issue)?;
+/// }
+/// ```
+pub trait ElicitationForm: Sized {
+    /// Returns the schema of the form.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the type has a field that cannot be part of a form, such as a nested
+    /// struct or a list.
+    fn form_schema() -> Schema;
+
+    /// Decodes the content of an accepted form.
+    fn from_form_content(content: HashMap<String, ElicitationResultContentValue>) -> Result<Self>;
+}
+
+impl<T: JsonSchema + DeserializeOwned> ElicitationForm for T {
+    fn form_schema() -> Schema {
+        let schema = SchemaSettings::default()
+            .with(|settings| settings.inline_subschemas = true)
+            .into_generator()
+            .into_root_schema_for::<T>();
+        let name = std::any::type_name::<T>();
+        let Some(properties) = schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .filter(|_| schema.get("type") == Some(&Value::from("object")))
+        else {
+            panic!("cannot ask for '{name}' in a form: its schema is not an object");
+        };
+
+        let properties = properties
+            .iter()
+            .map(|(field, property)| match primitive(property) {
+                Ok(definition) => (field.clone(), definition),
+                Err(reason) => {
+                    panic!("cannot ask for '{name}' in a form: field '{field}' {reason}")
+                }
+            })
+            .collect();
+        let required: Vec<String> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(String::from)
+            .collect();
+        Schema {
+            properties,
+            required: (!required.is_empty()).then_some(required),
+        }
+    }
+
+    fn from_form_content(content: HashMap<String, ElicitationResultContentValue>) -> Result<Self> {
+        let content: Map<String, Value> = content
+            .into_iter()
+            .map(|(field, value)| {
+                let value = match value {
+                    ElicitationResultContentValue::String(value) => Value::String(value),
+                    ElicitationResultContentValue::Bool(value) => Value::Bool(value),
+                    ElicitationResultContentValue::Number(value) => integral(value),
+                };
+                (field, value)
+            })
+            .collect();
+        serde_json::from_value(Value::Object(content)).map_err(|e| {
+            anyhow!(
+                "invalid form content for '{}': {e}",
+                std::any::type_name::<T>()
+            )
+        })
+    }
+}
+
+/// Asks the user to fill in the form for `T`, with `message` explaining what it is for.
+///
+/// Returns `None` if the user declines or cancels.
+pub fn elicit<T: ElicitationForm>(message: impl Into<String>) -> Result<Option<T>> {
+    let result = imports::create_elicitation(ElicitationRequestParamWithTimeout {
+        inner: ElicitationRequestParam::Form {
+            message: message.into(),
+            requested_schema: T::form_schema(),
+        },
+        timeout: None,
+    })
+    .context("failed to ask for form")?;
+
+    match result.action {
+        ElicitationResultAction::Accept => {
+            T::from_form_content(result.content.unwrap_or_default()).map(Some)
+        }
+        ElicitationResultAction::Decline | ElicitationResultAction::Cancel => Ok(None),
+    }
+}
+
+/// Converts the JSON Schema of a field into a form field, or says why it cannot be one.
+fn primitive(property: &Value) -> Result<PrimitiveSchemaDefinition, String> {
+    let mut property = property.as_object().cloned().unwrap_or_default();
+    strip_null(&mut property);
+    let text = |key: &str| property.get(key).and_then(Value::as_str).map(String::from);
+    let title = text("title");
+    let description = text("description");
+
+    if let Some((values, names)) = enum_values(&property)? {
+        return Ok(PrimitiveSchemaDefinition::Enum(EnumSchema {
+            description,
+            r#enum: values,
+            enum_names: names,
+            title,
+        }));
+    }
+
+    let r#type = property.get("type").and_then(Value::as_str);
+    Ok(match r#type {
+        Some("string") => PrimitiveSchemaDefinition::String(StringSchema {
+            description,
+            format: match text("format").as_deref() {
+                Some("email") => Some(StringSchemaFormat::Email),
+                Some("uri") => Some(StringSchemaFormat::Uri),
+                Some("date") => Some(StringSchemaFormat::Date),
+                Some("date-time") => Some(StringSchemaFormat::Datetime),
+                _ => None,
+            },
+            max_length: property.get("maxLength").and_then(Value::as_i64),
+            min_length: property.get("minLength").and_then(Value::as_i64),
+            title,
+        }),
+        Some(r#type @ ("number" | "integer")) => PrimitiveSchemaDefinition::Number(NumberSchema {
+            description,
+            maximum: property.get("maximum").and_then(Value::as_f64),
+            minimum: property.get("minimum").and_then(Value::as_f64),
+            title,
+            r#type: if r#type == "integer" {
+                NumberType::Integer
+            } else {
+                NumberType::Number
+            },
+        }),
+        Some("boolean") => PrimitiveSchemaDefinition::Boolean(BooleanSchema {
+            default: property.get("default").and_then(Value::as_bool),
+            description,
+            title,
+        }),
+        Some(r#type) => return Err(format!("has unsupported type '{type}'")),
+        None => return Err("has no single type".to_string()),
+    })
+}
+
+/// Removes `null` from the types a field's schema allows, which is how `Option` fields are
+/// described.
+fn strip_null(property: &mut Map<String, Value>) {
+    if let Some(Value::Array(types)) = property.get_mut("type") {
+        types.retain(|t| t != "null");
+        if let [only] = types.as_slice() {
+            let only = only.clone();
+            property.insert("type".to_string(), only);
+        }
+    }
+    if let Some(Value::Array(values)) = property.get_mut("enum") {
+        values.retain(|value| !value.is_null());
+    }
+    for key in ["anyOf", "oneOf"] {
+        let Some(Value::Array(variants)) = property.get_mut(key) else {
+            continue;
+        };
+        let count = variants.len();
+        variants.retain(|variant| variant.get("type") != Some(&Value::from("null")));
+        if let [only] = variants.as_slice()
+            && count > 1
+        {
+            let only = only.as_object().cloned().unwrap_or_default();
+            property.remove(key);
+            for (name, value) in only {
+                property.entry(name).or_insert(value);
+            }
+            strip_null(property);
+            return;
+        }
+    }
+}
+
+/// Returns the string values of an enum field and, if any variant is documented, their
+/// display names.
+#[allow(clippy::type_complexity)]
+fn enum_values(
+    property: &Map<String, Value>,
+) -> Result<Option<(Vec<String>, Option<Vec<String>>)>, String> {
+    let mut variants = Vec::new();
+    if let Some(value) = property.get("const") {
+        variants.push((value, None));
+    }
+    if let Some(Value::Array(values)) = property.get("enum") {
+        variants.extend(values.iter().map(|value| (value, None)));
+    }
+    for key in ["oneOf", "anyOf"] {
+        for variant in property
+            .get(key)
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let name = variant
+                .get("title")
+                .or_else(|| variant.get("description"))
+                .and_then(Value::as_str);
+            if let Some(value) = variant.get("const") {
+                variants.push((value, name));
+            } else if let Some(Value::Array(values)) = variant.get("enum") {
+                variants.extend(values.iter().map(|value| (value, name)));
+            } else {
+                return Err("is an enum with variants that are not plain strings".to_string());
+            }
+        }
+    }
+    if variants.is_empty() {
+        return Ok(None);
+    }
+
+    let values = variants
+        .iter()
+        .map(|(value, _)| value.as_str().map(String::from))
+        .collect::<Option<Vec<_>>>()
+        .ok_or("is an enum with values that are not strings")?;
+    let names = variants.iter().any(|(_, name)| name.is_some()).then(|| {
+        variants
+            .iter()
+            .zip(&values)
+            .map(|((_, name), value)| name.map_or_else(|| value.clone(), String::from))
+            .collect()
+    });
+    Ok(Some((values, names)))
+}
+
+/// Returns a whole number as an integer, since clients may send `3.0` for an integer field.
+fn integral(number: Number) -> Value {
+    match number.as_f64() {
+        Some(value)
+            if !number.is_i64()
+                && !number.is_u64()
+                && value.fract() == 0.0
+                && value.abs() < i64::MAX as f64 =>
+        {
+            Value::from(value as i64)
+        }
+        _ => Value::Number(number),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+    #[serde(rename_all = "lowercase")]
+    enum Priority {
+        Low,
+        High,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+    enum Size {
+        /// Small (up to a day)
+        Small,
+        /// Large (more than a day)
+        Large,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+    struct Issue {
+        /// One-line summary
+        title: String,
+        priority: Priority,
+        size: Option<Size>,
+        /// Who should work on it, if anyone
+        assignee: Option<String>,
+        estimate: Option<u32>,
+        urgent: bool,
+    }
+
+    fn schema<T: ElicitationForm>() -> Value {
+        serde_json::to_value(T::form_schema()).unwrap()
+    }
+
+    #[test]
+    fn fields_become_form_fields() {
+        let schema = schema::<Issue>();
+        let properties = &schema["properties"];
+        assert_eq!(
+            properties["title"],
+            json!({"type": "string", "description": "One-line summary"})
+        );
+        assert_eq!(
+            properties["assignee"],
+            json!({"type": "string", "description": "Who should work on it, if anyone"})
+        );
+        assert_eq!(properties["estimate"]["type"], "integer");
+        assert_eq!(properties["urgent"], json!({"type": "boolean"}));
+    }
+
+    #[test]
+    fn option_fields_are_not_required() {
+        let schema = schema::<Issue>();
+        let mut required: Vec<_> = schema["required"].as_array().unwrap().clone();
+        required.sort_by_key(|field| field.as_str().map(String::from));
+        assert_eq!(required, ["priority", "title", "urgent"]);
+    }
+
+    #[test]
+    fn unit_enums_become_enum_fields() {
+        let mut properties = Issue::form_schema().properties;
+        let Some(PrimitiveSchemaDefinition::Enum(priority)) = properties.remove("priority") else {
+            panic!("priority is not an enum");
+        };
+        assert_eq!(priority.r#enum, ["low", "high"]);
+        assert_eq!(priority.enum_names, None);
+
+        let Some(PrimitiveSchemaDefinition::Enum(size)) = properties.remove("size") else {
+            panic!("size is not an enum");
+        };
+        assert_eq!(size.r#enum, ["Small", "Large"]);
+        assert_eq!(
+            size.enum_names.unwrap(),
+            ["Small (up to a day)", "Large (more than a day)"]
+        );
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    struct Nested {
+        issue: Issue,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    struct Labels {
+        labels: Vec<String>,
+    }
+
+    #[test]
+    #[should_panic(expected = "field 'issue' has unsupported type 'object'")]
+    fn nested_structs_panic() {
+        Nested::form_schema();
+    }
+
+    #[test]
+    #[should_panic(expected = "field 'labels' has unsupported type 'array'")]
+    fn lists_panic() {
+        Labels::form_schema();
+    }
+
+    fn content(value: Value) -> HashMap<String, ElicitationResultContentValue> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn accepted_content_is_decoded() {
+        let issue = Issue::from_form_content(content(json!({
+            "title": "Crash on start",
+            "priority": "high",
+            "size": "Small",
+            "estimate": 3.0,
+            "urgent": true,
+        })))
+        .unwrap();
+        assert_eq!(
+            issue,
+            Issue {
+                title: "Crash on start".to_string(),
+                priority: Priority::High,
+                size: Some(Size::Small),
+                assignee: None,
+                estimate: Some(3),
+                urgent: true,
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_content_is_an_error() {
+        let error = Issue::from_form_content(content(json!({
+            "title": "Crash on start",
+            "priority": "urgent",
+            "urgent": true,
+        })))
+        .unwrap_err();
+        assert!(
+            error.to_string().starts_with("invalid form content for '"),
+            "{error}"
+        );
+        assert!(
+            error.to_string().contains("unknown variant `urgent`"),
+            "{error}"
+        );
+
+        let error = Issue::from_form_content(content(json!({
+            "title": "Crash on start",
+            "priority": "low",
+            "estimate": 2.5,
+            "urgent": false,
+        })))
+        .unwrap_err();
+        assert!(error.to_string().contains("2.5"), "{error}");
+    }
+}
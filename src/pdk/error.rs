@@ -0,0 +1,162 @@
+#![allow(unused)]
+use super::cancellation::Cancelled;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::fmt;
+
+/// A JSON-RPC error to report to the client.
+///
+/// Handlers return it through `anyhow` like any other error; when an export fails,
+/// `return_error` looks for an `McpError` in the error chain and hands it to the host as
+/// JSON, so hyper-mcp can answer with the right JSON-RPC code. Any other error is reported
+/// as an [internal error](McpError::internal_error) carrying the full error chain, except
+/// for a [`Cancelled`] request, which is reported as [cancelled](McpError::request_cancelled).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct McpError {
+    /// The JSON-RPC error code
+    pub code: i64,
+
+    /// A short description of the error
+    pub message: String,
+
+    /// Additional information about the error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub data: Option<Value>,
+}
+
+impl McpError {
+    /// The JSON-RPC code for an invalid method name.
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+
+    /// The JSON-RPC code for invalid method parameters.
+    pub const INVALID_PARAMS: i64 = -32602;
+
+    /// The JSON-RPC code for an internal error.
+    pub const INTERNAL_ERROR: i64 = -32603;
+
+    /// The MCP code for an unknown resource.
+    pub const RESOURCE_NOT_FOUND: i64 = -32002;
+
+    /// The code for a request the client cancelled, as in the Language Server Protocol.
+    ///
+    /// The client does not expect a response to a cancelled request, so the host should
+    /// drop errors with this code instead of answering with them.
+    pub const REQUEST_CANCELLED: i64 = -32800;
+
+    /// Creates an error with the given code and message.
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        McpError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    /// Attaches additional information to the error.
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    /// The request's parameters are invalid, e.g. an unknown tool or a missing argument.
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        McpError::new(Self::INVALID_PARAMS, message)
+    }
+
+    /// The requested method is not supported.
+    pub fn method_not_found(method: &str) -> Self {
+        McpError::new(
+            Self::METHOD_NOT_FOUND,
+            format!("Method not found: {method}"),
+        )
+        .with_data(json!({ "method": method }))
+    }
+
+    /// No resource exists at `uri`.
+    pub fn resource_not_found(uri: &str) -> Self {
+        McpError::new(
+            Self::RESOURCE_NOT_FOUND,
+            format!("Resource not found: {uri}"),
+        )
+        .with_data(json!({ "uri": uri }))
+    }
+
+    /// The client cancelled the request, giving `reason` if any.
+    pub fn request_cancelled(reason: Option<&str>) -> Self {
+        let error = McpError::new(Self::REQUEST_CANCELLED, "Request cancelled");
+        match reason {
+            Some(reason) => error.with_data(json!({ "reason": reason })),
+            None => error,
+        }
+    }
+
+    /// Something failed inside the plugin.
+    pub fn internal_error(message: impl Into<String>) -> Self {
+        McpError::new(Self::INTERNAL_ERROR, message)
+    }
+}
+
+impl fmt::Display for McpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for McpError {}
+
+impl From<&anyhow::Error> for McpError {
+    /// Finds the outermost `McpError` or [`Cancelled`] in the chain, or wraps the whole chain
+    /// as an internal error.
+    fn from(e: &anyhow::Error) -> Self {
+        e.chain()
+            .find_map(|cause| {
+                cause.downcast_ref::<McpError>().cloned().or_else(|| {
+                    cause
+                        .downcast_ref::<Cancelled>()
+                        .map(|c| McpError::request_cancelled(c.reason.as_deref()))
+                })
+            })
+            .unwrap_or_else(|| McpError::internal_error(format!("{e:#}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context, anyhow};
+
+    #[test]
+    fn the_outermost_mcp_error_is_reported() {
+        let error = Err::<(), _>(McpError::invalid_params("bad cursor"))
+            .context("listing tools")
+            .unwrap_err();
+        assert_eq!(
+            McpError::from(&error),
+            McpError::invalid_params("bad cursor")
+        );
+    }
+
+    #[test]
+    fn cancellations_are_reported_as_cancelled() {
+        let error = anyhow::Error::new(Cancelled {
+            reason: Some("user pressed stop".to_string()),
+        })
+        .context("while crawling");
+        let error = McpError::from(&error);
+        assert_eq!(error.code, McpError::REQUEST_CANCELLED);
+        assert_eq!(error.data, Some(json!({ "reason": "user pressed stop" })));
+
+        let error = McpError::from(&anyhow::Error::new(Cancelled { reason: None }));
+        assert_eq!(error, McpError::request_cancelled(None));
+        assert_eq!(error.data, None);
+    }
+
+    #[test]
+    fn other_errors_are_internal_errors_with_the_chain() {
+        let error = anyhow!("connection refused").context("fetching forecast");
+        let error = McpError::from(&error);
+        assert_eq!(error.code, McpError::INTERNAL_ERROR);
+        assert_eq!(error.message, "fetching forecast: connection refused");
+    }
+}
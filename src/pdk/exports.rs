@@ -1,12 +1,110 @@
+use super::{
+    cancellation, completion, error::McpError, imports, logging, prompts, resources, tools,
+    types::*,
+};
 use extism_pdk::{Error, Json, Memory, extism::error_set, input, output};
+use serde_json::json;
+use std::{
+    panic::{self, PanicHookInfo},
+    sync::Once,
+};
 
+/// Reports `e` to the host as a JSON-serialized [`McpError`] and returns the failure code.
 pub(crate) fn return_error(e: Error) -> i32 {
-    let err = format!("{e:?}");
+    set_error(&McpError::from(&e));
+    -1
+}
+
+fn set_error(error: &McpError) {
+    let err = serde_json::to_string(error).unwrap();
     let mem = Memory::from_bytes(&err).unwrap();
     unsafe {
         error_set(mem.offset());
     }
-    -1
+}
+
+/// Runs an export's body, turning a panic into an MCP internal error.
+///
+/// The error carries the panic message and location, is logged to the client at `Critical`
+/// level and is set as the call's error. Wasm builds abort on panic, so there the panic
+/// hook reports the error itself before the host sees the trap; only native builds, which
+/// unwind, catch the panic and fail the export normally.
+///
+/// The first call also installs the panic hook and the pdk's logger; see [`logging::init`].
+fn catch_panics(body: impl FnOnce() -> i32) -> i32 {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        panic::set_hook(Box::new(report_panic));
+        logging::init();
+    });
+
+    catch(body).unwrap_or_else(|error| {
+        report(&error);
+        -1
+    })
+}
+
+#[cfg(panic = "unwind")]
+thread_local! {
+    /// The error of the panic being unwound, recorded by the panic hook for [`catch`].
+    static PANIC: std::cell::RefCell<Option<McpError>> = const { std::cell::RefCell::new(None) };
+}
+
+/// Runs `body`, returning the error of a panic instead of unwinding.
+#[cfg(panic = "unwind")]
+fn catch(body: impl FnOnce() -> i32) -> Result<i32, McpError> {
+    panic::catch_unwind(panic::AssertUnwindSafe(body)).map_err(|payload| {
+        PANIC
+            .take()
+            .unwrap_or_else(|| panic_error(panic_message(payload.as_ref()), None))
+    })
+}
+
+/// Runs `body`; a panic aborts after [`report_panic`] has reported it.
+#[cfg(not(panic = "unwind"))]
+fn catch(body: impl FnOnce() -> i32) -> Result<i32, McpError> {
+    Ok(body())
+}
+
+fn report_panic(info: &PanicHookInfo<'_>) {
+    let location = info
+        .location()
+        .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()));
+    let error = panic_error(panic_message(info.payload()), location);
+
+    #[cfg(panic = "unwind")]
+    PANIC.set(Some(error));
+    #[cfg(not(panic = "unwind"))]
+    report(&error);
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>")
+}
+
+fn panic_error(message: &str, location: Option<String>) -> McpError {
+    McpError::internal_error(match &location {
+        Some(location) => format!("plugin panicked at {location}: {message}"),
+        None => format!("plugin panicked: {message}"),
+    })
+    .with_data(json!({ "panic": message, "location": location }))
+}
+
+/// Logs the error of a panic to the client and sets it as the call's error.
+fn report(error: &McpError) {
+    if logging::enabled(LoggingLevel::Critical) {
+        let location = error.data.as_ref().and_then(|data| data.get("location"));
+        let _ = imports::notify_logging_message(LoggingMessageNotificationParam {
+            data: json!({ "message": error.message, "location": location }),
+            level: LoggingLevel::Critical,
+            logger: Some(env!("CARGO_PKG_NAME").to_string()),
+        });
+    }
+    set_error(error);
 }
 
 macro_rules! try_input_json {
@@ -14,97 +112,198 @@ macro_rules! try_input_json {
         let x = input();
         match x {
             Ok(Json(x)) => x,
-            Err(e) => return return_error(e),
+            Err(e) => return return_error(McpError::invalid_params(format!("{e:#}")).into()),
         }
     }};
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn call_tool() -> i32 {
-    let ret = crate::call_tool(try_input_json!()).and_then(|x| output(Json(x)));
+    catch_panics(|| {
+        let input: CallToolRequest = try_input_json!();
+        let ret = cancellation::reject_cancelled(&input.context)
+            .and_then(|()| tools::registry().call(input).and_then(|x| output(Json(x))));
 
-    match ret {
-        Ok(()) => 0,
-        Err(e) => return_error(e),
-    }
+        match ret {
+            Ok(()) => 0,
+            Err(e) => return_error(e),
+        }
+    })
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn complete() -> i32 {
-    let ret = crate::complete(try_input_json!()).and_then(|x| output(Json(x)));
+    catch_panics(|| {
+        let input: CompleteRequest = try_input_json!();
+        let ret = cancellation::reject_cancelled(&input.context)
+            .and_then(|()| completion::complete(input).and_then(|x| output(Json(x))));
 
-    match ret {
-        Ok(()) => 0,
-        Err(e) => return_error(e),
-    }
+        match ret {
+            Ok(()) => 0,
+            Err(e) => return_error(e),
+        }
+    })
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn get_prompt() -> i32 {
-    let ret = crate::get_prompt(try_input_json!()).and_then(|x| output(Json(x)));
+    catch_panics(|| {
+        let input: GetPromptRequest = try_input_json!();
+        let ret = cancellation::reject_cancelled(&input.context).and_then(|()| {
+            prompts::registry()
+                .render(input)
+                .and_then(|x| output(Json(x)))
+        });
 
-    match ret {
-        Ok(()) => 0,
-        Err(e) => return_error(e),
-    }
+        match ret {
+            Ok(()) => 0,
+            Err(e) => return_error(e),
+        }
+    })
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn list_prompts() -> i32 {
-    let ret = crate::list_prompts(try_input_json!()).and_then(|x| output(Json(x)));
+    catch_panics(|| {
+        let ret = prompts::registry()
+            .list(try_input_json!())
+            .and_then(|x| output(Json(x)));
 
-    match ret {
-        Ok(()) => 0,
-        Err(e) => return_error(e),
-    }
+        match ret {
+            Ok(()) => 0,
+            Err(e) => return_error(e),
+        }
+    })
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn list_resource_templates() -> i32 {
-    let ret = crate::list_resource_templates(try_input_json!()).and_then(|x| output(Json(x)));
+    catch_panics(|| {
+        let ret = resources::registry()
+            .list_templates(try_input_json!())
+            .and_then(|x| output(Json(x)));
 
-    match ret {
-        Ok(()) => 0,
-        Err(e) => return_error(e),
-    }
+        match ret {
+            Ok(()) => 0,
+            Err(e) => return_error(e),
+        }
+    })
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn list_resources() -> i32 {
-    let ret = crate::list_resources(try_input_json!()).and_then(|x| output(Json(x)));
+    catch_panics(|| {
+        let ret = resources::registry()
+            .list(try_input_json!())
+            .and_then(|x| output(Json(x)));
 
-    match ret {
-        Ok(()) => 0,
-        Err(e) => return_error(e),
-    }
+        match ret {
+            Ok(()) => 0,
+            Err(e) => return_error(e),
+        }
+    })
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn list_tools() -> i32 {
-    let ret = crate::list_tools(try_input_json!()).and_then(|x| output(Json(x)));
+    catch_panics(|| {
+        let ret = tools::registry()
+            .list(try_input_json!())
+            .and_then(|x| output(Json(x)));
 
-    match ret {
-        Ok(()) => 0,
-        Err(e) => return_error(e),
-    }
+        match ret {
+            Ok(()) => 0,
+            Err(e) => return_error(e),
+        }
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn on_cancelled() -> i32 {
+    catch_panics(|| {
+        cancellation::record(try_input_json!());
+        0
+    })
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn on_roots_list_changed() -> i32 {
-    let ret = crate::on_roots_list_changed(try_input_json!()).and_then(output);
+    catch_panics(|| {
+        let ret = crate::on_roots_list_changed(try_input_json!()).and_then(output);
 
-    match ret {
-        Ok(()) => 0,
-        Err(e) => return_error(e),
-    }
+        match ret {
+            Ok(()) => 0,
+            Err(e) => return_error(e),
+        }
+    })
 }
 
 #[unsafe(no_mangle)]
 pub extern "C" fn read_resource() -> i32 {
-    let ret = crate::read_resource(try_input_json!()).and_then(|x| output(Json(x)));
+    catch_panics(|| {
+        let input: ReadResourceRequest = try_input_json!();
+        let ret = cancellation::reject_cancelled(&input.context).and_then(|()| {
+            resources::registry()
+                .read(input)
+                .and_then(|x| output(Json(x)))
+        });
+
+        match ret {
+            Ok(()) => 0,
+            Err(e) => return_error(e),
+        }
+    })
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn set_logging_level() -> i32 {
+    catch_panics(|| {
+        let SetLevelRequest { request, .. } = try_input_json!();
+        logging::set_level(request.level);
+        0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn returns_the_result_of_a_body_that_does_not_panic() {
+        assert_eq!(catch(|| 0).unwrap(), 0);
+        assert_eq!(catch(|| -1).unwrap(), -1);
+    }
+
+    #[test]
+    fn a_panicking_handler_returns_an_internal_error() {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(report_panic));
+        let error = catch(|| panic!("index {} out of range", 3)).unwrap_err();
+        let line = line!() - 1;
+        panic::set_hook(hook);
+
+        assert_eq!(error.code, McpError::INTERNAL_ERROR);
+        let location = format!("{}:{line}:", file!());
+        assert!(
+            error
+                .message
+                .starts_with(&format!("plugin panicked at {location}")),
+            "{}",
+            error.message
+        );
+        assert!(error.message.ends_with(": index 3 out of range"));
+        let data = error.data.unwrap();
+        assert_eq!(data["panic"], "index 3 out of range");
+        assert!(data["location"].as_str().unwrap().starts_with(&location));
+    }
 
-    match ret {
-        Ok(()) => 0,
-        Err(e) => return_error(e),
+    #[test]
+    fn a_panic_without_the_hook_still_returns_its_message() {
+        // Another test may have the hook installed meanwhile, which adds the location.
+        let error = catch(|| std::panic::panic_any("no hook")).unwrap_err();
+        assert_eq!(error.code, McpError::INTERNAL_ERROR);
+        assert!(error.message.starts_with("plugin panicked"));
+        assert!(error.message.ends_with(": no hook"));
+        assert_eq!(error.data.unwrap()["panic"], "no hook");
     }
 }
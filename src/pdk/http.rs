@@ -1,67 +1,417 @@
-use anyhow::Result;
-use chrono::{DateTime, Utc};
+#![allow(unused)]
+use super::rate_limit::{self, RateLimit};
+use anyhow::{Context, Result};
 use extism_pdk::*;
-use std::{thread, time::Duration};
-
-#[allow(dead_code)]
-pub(crate) fn http_request_with_retry(req: &HttpRequest) -> Result<HttpResponse> {
-    fn parse_retry_after(value: &str) -> Option<Duration> {
-        if let Ok(secs) = value.parse::<u64>() {
-            Some(Duration::from_secs(secs))
-        } else if let Ok(date) = DateTime::parse_from_rfc2822(value) {
-            let target = date.with_timezone(&Utc);
-            let now = Utc::now();
-            if target > now {
-                let delta = target - now;
-                delta.to_std().ok()
-            } else {
-                None
-            }
-        } else if let Ok(date) =
-            chrono::NaiveDateTime::parse_from_str(value, "%a %b %e %H:%M:%S %Y")
-        {
-            let target = date.and_utc();
-            let now = Utc::now();
-            if target > now {
-                let delta = target - now;
-                delta.to_std().ok()
-            } else {
-                None
-            }
-        } else {
-            None
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Extism config key holding the plugin-wide [`RetryPolicy`] as JSON.
+///
+/// Fields use the names below, with durations in milliseconds (`initial_delay_ms`,
+/// `max_delay_ms`, `budget_ms`); missing fields keep their defaults, e.g.
+/// `{"max_attempts": 5, "budget_ms": 10000}`.
+pub const RETRY_POLICY_CONFIG_KEY: &str = "http_retry_policy";
+
+/// How a failed HTTP request is retried.
+///
+/// Transport errors and responses whose status is in `retry_statuses` are retried until
+/// `max_attempts` have been made. The delay before retry `n` is
+/// `initial_delay * multiplier^(n - 1)`, capped at `max_delay`, with up to `jitter` of it
+/// randomized so that clients do not retry in lockstep. A `Retry-After` header, or an
+/// exhausted `RateLimit-Remaining` with its reset time, takes precedence over the computed
+/// delay. No retry is started if it would finish waiting after `budget` has elapsed since
+/// the first attempt; the last response or error is returned instead.
+///
+/// Rate limits announced by any response are remembered per host, and later requests to
+/// that host wait for the limit to reset before they are sent, or fail right away if the
+/// wait would exceed what is left of the budget.
+///
+/// Only idempotent requests are retried: `GET`, `HEAD`, `PUT`, `DELETE`, `OPTIONS` and
+/// `TRACE`, plus any request carrying an `Idempotency-Key` header. With
+/// `idempotency_keys`, `POST` and `PATCH` requests without one get a fresh key that is sent
+/// with every attempt, so the server can discard duplicates. Other requests are sent once
+/// unless `retry_non_idempotent` is set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first
+    pub max_attempts: u32,
+
+    /// Delay before the first retry
+    #[serde(rename = "initial_delay_ms", with = "millis")]
+    pub initial_delay: Duration,
+
+    /// Factor applied to the delay after each retry
+    pub multiplier: f64,
+
+    /// Upper bound for a computed delay
+    #[serde(rename = "max_delay_ms", with = "millis")]
+    pub max_delay: Duration,
+
+    /// Fraction of each delay, from 0 to 1, that is randomized
+    pub jitter: f64,
+
+    /// Total time allowed for all attempts and delays, or `None` for no limit
+    #[serde(rename = "budget_ms", with = "option_millis")]
+    pub budget: Option<Duration>,
+
+    /// Response status codes that are retried
+    pub retry_statuses: BTreeSet<u16>,
+
+    /// Whether to add a generated `Idempotency-Key` header to `POST` and `PATCH` requests
+    pub idempotency_keys: bool,
+
+    /// Whether to retry requests that are not known to be idempotent
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(500),
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(15),
+            jitter: 0.5,
+            budget: Some(Duration::from_secs(60)),
+            retry_statuses: BTreeSet::from([408, 429, 500, 502, 503, 504]),
+            idempotency_keys: false,
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that makes a single attempt.
+    pub fn never() -> Self {
+        RetryPolicy::default().with_max_attempts(1)
+    }
+
+    /// Reads the plugin-wide policy from the [`RETRY_POLICY_CONFIG_KEY`] config entry,
+    /// falling back to the default policy if it is not set.
+    pub fn from_config() -> Result<Self> {
+        match config::get(RETRY_POLICY_CONFIG_KEY)? {
+            Some(json) => serde_json::from_str(&json)
+                .with_context(|| format!("invalid '{RETRY_POLICY_CONFIG_KEY}' config")),
+            None => Ok(RetryPolicy::default()),
         }
     }
 
-    const MAX_HTTP_ATTEMPTS: u32 = 3;
-    const RETRY_DELAY: Duration = Duration::from_secs(15);
+    /// Sets the maximum number of attempts, including the first (at least 1).
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Sets the delay before the first retry, the growth factor and the delay cap.
+    pub fn with_backoff(
+        mut self,
+        initial_delay: Duration,
+        multiplier: f64,
+        max_delay: Duration,
+    ) -> Self {
+        self.initial_delay = initial_delay;
+        self.multiplier = multiplier.max(1.0);
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Sets the randomized fraction of each delay, clamped to 0..=1.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the total time budget, or removes it with `None`.
+    pub fn with_budget(mut self, budget: Option<Duration>) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Sets the response status codes that are retried.
+    pub fn with_retry_statuses(mut self, statuses: impl IntoIterator<Item = u16>) -> Self {
+        self.retry_statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Sets whether `POST` and `PATCH` requests get a generated `Idempotency-Key` header,
+    /// which makes them retryable.
+    pub fn with_idempotency_keys(mut self, enabled: bool) -> Self {
+        self.idempotency_keys = enabled;
+        self
+    }
+
+    /// Sets whether requests that are not known to be idempotent are retried anyway.
+    ///
+    /// Only enable this for endpoints where a duplicate request is harmless.
+    pub fn with_non_idempotent_retries(mut self, enabled: bool) -> Self {
+        self.retry_non_idempotent = enabled;
+        self
+    }
+
+    /// Returns whether `req` may be sent more than once under this policy.
+    pub fn can_retry(&self, req: &HttpRequest) -> bool {
+        self.retry_non_idempotent
+            || is_idempotent(req)
+            || header(req, IDEMPOTENCY_KEY_HEADER).is_some()
+    }
+
+    /// Returns the jittered delay to wait after `attempts` failed attempts.
+    ///
+    /// The backoff is computed in floating point and capped at `max_delay`, so large
+    /// attempt counts, multipliers or delays saturate instead of overflowing.
+    pub fn delay(&self, attempts: u32) -> Duration {
+        let exponent = attempts.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let factor = self.multiplier.max(1.0).powi(exponent).min(f64::MAX);
+        let base = (self.initial_delay.as_secs_f64() * factor).min(self.max_delay.as_secs_f64());
+        let delay = base * (1.0 - self.jitter.clamp(0.0, 1.0) * random_unit());
+        Duration::try_from_secs_f64(delay).unwrap_or(self.max_delay)
+    }
+
+    /// Returns whether waiting `delay` after `elapsed` still fits the budget.
+    fn allows(&self, elapsed: Duration, delay: Duration) -> bool {
+        self.budget
+            .is_none_or(|budget| elapsed.saturating_add(delay) < budget)
+    }
+}
+
+/// The header carrying a client-chosen key that lets servers deduplicate retried requests.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Returns the request's method in upper case, defaulting to `GET`.
+fn method(req: &HttpRequest) -> String {
+    req.method.as_deref().unwrap_or("GET").to_ascii_uppercase()
+}
+
+/// Returns whether the request's method is idempotent by definition (RFC 9110).
+fn is_idempotent(req: &HttpRequest) -> bool {
+    matches!(
+        method(req).as_str(),
+        "GET" | "HEAD" | "PUT" | "DELETE" | "OPTIONS" | "TRACE"
+    )
+}
+
+/// Looks up a request header case-insensitively.
+fn header<'a>(req: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    req.headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
 
+/// Generates a random `Idempotency-Key` value (a UUID v4).
+pub fn idempotency_key() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("the host provides randomness");
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Returns a random number in `[0, 1)`.
+fn random_unit() -> f64 {
+    let mut bytes = [0; 8];
+    if getrandom::getrandom(&mut bytes).is_err() {
+        return 0.5;
+    }
+    (u64::from_le_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+mod millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(value.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
+
+mod option_millis {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_some(&(value.as_millis() as u64)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<u64>::deserialize(deserializer).map(|ms| ms.map(Duration::from_millis))
+    }
+}
+
+/// Sends `req` with an optional `body`, retrying according to the plugin-wide policy from [`RetryPolicy::from_config`].
+pub(crate) fn http_request_with_retry(
+    req: &HttpRequest,
+    body: Option<&[u8]>,
+) -> Result<HttpResponse> {
+    http_request_with_policy(req, body, &RetryPolicy::from_config()?)
+}
+
+/// Sends `req` with an optional `body`, retrying according to `policy`.
+///
+/// Requests that [cannot be retried](RetryPolicy::can_retry) are sent exactly once.
+pub(crate) fn http_request_with_policy(
+    req: &HttpRequest,
+    body: Option<&[u8]>,
+    policy: &RetryPolicy,
+) -> Result<HttpResponse> {
+    let mut keyed;
+    let mut req = req;
+    if policy.idempotency_keys
+        && matches!(method(req).as_str(), "POST" | "PATCH")
+        && header(req, IDEMPOTENCY_KEY_HEADER).is_none()
+    {
+        keyed = req.clone();
+        keyed
+            .headers
+            .insert(IDEMPOTENCY_KEY_HEADER.to_string(), idempotency_key());
+        req = &keyed;
+    }
+    let max_attempts = if policy.can_retry(req) {
+        policy.max_attempts
+    } else {
+        1
+    };
+
+    let started = Instant::now();
     let mut attempt = 0;
 
     loop {
         attempt += 1;
-        match http::request::<()>(req, None) {
-            Ok(res) => {
-                let status = res.status_code();
-
-                if attempt < MAX_HTTP_ATTEMPTS && (status == 429 || status >= 500) {
-                    thread::sleep(
-                        res.header("retry-after")
-                            .or_else(|| res.header("Retry-After"))
-                            .and_then(parse_retry_after)
-                            .unwrap_or(RETRY_DELAY),
-                    );
-                    continue;
-                }
-                break Ok(res);
-            }
-            Err(e) => {
-                if attempt < MAX_HTTP_ATTEMPTS {
-                    thread::sleep(RETRY_DELAY);
-                    continue;
-                }
-                break Err(e);
+        let remaining = policy
+            .budget
+            .map(|budget| budget.saturating_sub(started.elapsed()));
+        rate_limit::wait_for(&req.url, remaining)?;
+
+        let result = http::request(req, body);
+        if let Ok(res) = &result {
+            rate_limit::record(&req.url, res);
+        }
+        let server_delay = match &result {
+            Ok(res) if policy.retry_statuses.contains(&res.status_code()) => {
+                RateLimit::from_response(res).wait()
             }
+            Ok(_) => return result,
+            Err(_) => None,
+        };
+
+        let delay = server_delay.unwrap_or_else(|| policy.delay(attempt));
+        if attempt >= max_attempts || !policy.allows(started.elapsed(), delay) {
+            return result;
         }
+        thread::sleep(delay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steady(initial: Duration, multiplier: f64, max: Duration) -> RetryPolicy {
+        RetryPolicy::default()
+            .with_backoff(initial, multiplier, max)
+            .with_jitter(0.0)
+    }
+
+    #[test]
+    fn delay_grows_exponentially_up_to_the_cap() {
+        let policy = steady(Duration::from_millis(100), 2.0, Duration::from_secs(1));
+        let delays: Vec<_> = (1..=6).map(|attempt| policy.delay(attempt)).collect();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 800, 1000, 1000].map(Duration::from_millis)
+        );
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn delay_saturates_instead_of_overflowing() {
+        let max = Duration::from_secs(30);
+        let policy = steady(Duration::from_secs(1), 1e300, max);
+        assert_eq!(policy.delay(u32::MAX), max);
+
+        let policy = steady(Duration::MAX, 2.0, max);
+        assert_eq!(policy.delay(2), max);
+
+        let policy = steady(Duration::ZERO, 1e300, max);
+        assert_eq!(policy.delay(u32::MAX), Duration::ZERO);
+
+        let policy = steady(Duration::MAX, 2.0, Duration::MAX);
+        assert_eq!(policy.delay(u32::MAX), Duration::MAX);
+    }
+
+    #[test]
+    fn jitter_only_shortens_the_delay() {
+        let policy = RetryPolicy::default()
+            .with_backoff(Duration::from_secs(1), 2.0, Duration::from_secs(10))
+            .with_jitter(1.0);
+        for attempt in 1..50 {
+            assert!(policy.delay(attempt) <= Duration::from_secs(10));
+        }
+    }
+
+    #[test]
+    fn only_idempotent_or_keyed_requests_are_retried() {
+        let policy = RetryPolicy::default();
+        let get = HttpRequest::new("https://example.com");
+        let post = HttpRequest::new("https://example.com").with_method("post");
+        let keyed = HttpRequest::new("https://example.com")
+            .with_method("POST")
+            .with_header("idempotency-key", idempotency_key());
+        assert!(policy.can_retry(&get));
+        assert!(!policy.can_retry(&post));
+        assert!(policy.can_retry(&keyed));
+        assert!(
+            RetryPolicy::default()
+                .with_non_idempotent_retries(true)
+                .can_retry(&post)
+        );
+    }
+
+    #[test]
+    fn budget_limits_the_last_delay() {
+        let policy = RetryPolicy::default().with_budget(Some(Duration::from_secs(10)));
+        assert!(policy.allows(Duration::from_secs(5), Duration::from_secs(4)));
+        assert!(!policy.allows(Duration::from_secs(5), Duration::from_secs(5)));
+        assert!(
+            RetryPolicy::default()
+                .with_budget(None)
+                .allows(Duration::MAX, Duration::MAX)
+        );
+    }
+
+    #[test]
+    fn policy_config_uses_milliseconds_and_defaults() {
+        let policy: RetryPolicy =
+            serde_json::from_str(r#"{"max_attempts": 5, "max_delay_ms": 2500, "budget_ms": null}"#)
+                .unwrap();
+        assert_eq!(policy.max_attempts, 5);
+        assert_eq!(policy.max_delay, Duration::from_millis(2500));
+        assert_eq!(policy.budget, None);
+        assert_eq!(policy.initial_delay, RetryPolicy::default().initial_delay);
     }
 }
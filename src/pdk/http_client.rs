@@ -0,0 +1,384 @@
+#![allow(unused)]
+use super::{
+    http::{RetryPolicy, http_request_with_policy, http_request_with_retry},
+    oauth::BearerAuth,
+    types::OauthCredentials,
+    uri_template,
+};
+use anyhow::{Context, Result};
+use extism_pdk::{HttpRequest, HttpResponse};
+use serde::{Serialize, de::DeserializeOwned};
+use std::{collections::BTreeMap, fmt};
+
+/// Maximum number of characters of a response body quoted in an [`HttpStatusError`].
+const MAX_ERROR_BODY_CHARS: usize = 2048;
+
+/// A small HTTP client over Extism's host HTTP functions.
+///
+/// Holds the settings shared by the requests it builds: an optional base URL that relative
+/// paths are joined to, default headers, and an optional [`RetryPolicy`] (otherwise the
+/// plugin-wide policy applies), and optionally [`BearerAuth`] to authenticate every
+/// request. Every request goes through `http_request_with_retry`, and a response with a
+/// non-success status is turned into an [`HttpStatusError`].
+///
+/// ```ignore
+/// let client = HttpClient::new()
+///     .with_base_url("https://api.example.com/v1")
+///     .with_header("Accept", "application/json");
+/// let issue: Issue = client
+///     .post("issues")
+///     .query("notify", "false")
+///     .json(&NewIssue { title })
+///     .send_json()?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct HttpClient {
+    base_url: Option<String>,
+    headers: BTreeMap<String, String>,
+    retry_policy: Option<RetryPolicy>,
+    auth: Option<BearerAuth>,
+}
+
+impl HttpClient {
+    /// Creates a client without a base URL or default headers.
+    pub fn new() -> Self {
+        HttpClient::default()
+    }
+
+    /// Sets the URL that relative request paths are joined to.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Adds a header sent with every request.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets the retry policy for this client's requests instead of the plugin-wide one.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Authenticates every request with a bearer token obtained from the host for
+    /// `credentials`; see [`BearerAuth`].
+    pub fn with_bearer_auth(mut self, credentials: OauthCredentials) -> Self {
+        self.auth = Some(BearerAuth::new(credentials));
+        self
+    }
+
+    /// Starts a request with the given method to `path`, which is either an absolute URL
+    /// or a path relative to the base URL.
+    pub fn request(&self, method: &str, path: &str) -> RequestBuilder<'_> {
+        RequestBuilder {
+            client: self,
+            method: method.to_ascii_uppercase(),
+            url: self.url(path),
+            query: Vec::new(),
+            headers: self.headers.clone(),
+            body: None,
+            retry_policy: self.retry_policy.clone(),
+        }
+    }
+
+    /// Starts a `GET` request.
+    pub fn get(&self, path: &str) -> RequestBuilder<'_> {
+        self.request("GET", path)
+    }
+
+    /// Starts a `POST` request.
+    pub fn post(&self, path: &str) -> RequestBuilder<'_> {
+        self.request("POST", path)
+    }
+
+    /// Starts a `PUT` request.
+    pub fn put(&self, path: &str) -> RequestBuilder<'_> {
+        self.request("PUT", path)
+    }
+
+    /// Starts a `PATCH` request.
+    pub fn patch(&self, path: &str) -> RequestBuilder<'_> {
+        self.request("PATCH", path)
+    }
+
+    /// Starts a `DELETE` request.
+    pub fn delete(&self, path: &str) -> RequestBuilder<'_> {
+        self.request("DELETE", path)
+    }
+
+    fn url(&self, path: &str) -> String {
+        match &self.base_url {
+            Some(base) if !path.contains("://") => {
+                if path.is_empty() {
+                    base.clone()
+                } else {
+                    format!(
+                        "{}/{}",
+                        base.trim_end_matches('/'),
+                        path.trim_start_matches('/')
+                    )
+                }
+            }
+            _ => path.to_string(),
+        }
+    }
+}
+
+/// A request being built by an [`HttpClient`].
+#[must_use = "a request does nothing until it is sent"]
+pub struct RequestBuilder<'a> {
+    client: &'a HttpClient,
+    method: String,
+    url: String,
+    query: Vec<(String, String)>,
+    headers: BTreeMap<String, String>,
+    body: Option<Result<Vec<u8>>>,
+    retry_policy: Option<RetryPolicy>,
+}
+
+impl RequestBuilder<'_> {
+    /// Adds a header, replacing a default header of the same name.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Appends a query parameter; names and values are percent-encoded.
+    pub fn query(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.query.push((name.into(), value.to_string()));
+        self
+    }
+
+    /// Appends query parameters; names and values are percent-encoded.
+    pub fn query_pairs<K, V>(mut self, pairs: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: ToString,
+    {
+        self.query
+            .extend(pairs.into_iter().map(|(k, v)| (k.into(), v.to_string())));
+        self
+    }
+
+    /// Sets a raw body and its content type.
+    pub fn body(mut self, body: impl Into<Vec<u8>>, content_type: &str) -> Self {
+        self.body = Some(Ok(body.into()));
+        self.header("Content-Type", content_type)
+    }
+
+    /// Sets `body` serialized as JSON; a serialization failure is reported by `send`.
+    pub fn json<T: Serialize + ?Sized>(mut self, body: &T) -> Self {
+        self.body = Some(serde_json::to_vec(body).context("failed to serialize request body"));
+        self.header("Content-Type", "application/json")
+    }
+
+    /// Sets the retry policy for this request only.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Returns the full URL including the encoded query string.
+    pub fn url(&self) -> String {
+        if self.query.is_empty() {
+            return self.url.clone();
+        }
+        let mut url = self.url.clone();
+        let mut separator = if url.contains('?') { '&' } else { '?' };
+        for (name, value) in &self.query {
+            url.push(separator);
+            uri_template::encode(name, false, &mut url);
+            url.push('=');
+            uri_template::encode(value, false, &mut url);
+            separator = '&';
+        }
+        url
+    }
+
+    /// Sends the request, failing with an [`HttpStatusError`] unless the final response
+    /// has a 2xx status.
+    ///
+    /// With bearer authentication, a `401 Unauthorized` response is retried once with a
+    /// newly requested token.
+    pub fn send(self) -> Result<HttpResponse> {
+        let mut req = HttpRequest::new(self.url()).with_method(&self.method);
+        req.headers = self.headers;
+        let body = self.body.transpose()?;
+
+        let send = |req: &HttpRequest| {
+            match &self.retry_policy {
+                Some(policy) => http_request_with_policy(req, body.as_deref(), policy),
+                None => http_request_with_retry(req, body.as_deref()),
+            }
+            .with_context(|| format!("{} {} failed", self.method, req.url))
+        };
+
+        let res = match &self.client.auth {
+            Some(auth) => {
+                req.headers.insert(
+                    "Authorization".to_string(),
+                    BearerAuth::header_value(&auth.token()?),
+                );
+                let res = send(&req)?;
+                if res.status_code() == 401 {
+                    req.headers.insert(
+                        "Authorization".to_string(),
+                        BearerAuth::header_value(&auth.refresh()?),
+                    );
+                    send(&req)?
+                } else {
+                    res
+                }
+            }
+            None => send(&req)?,
+        };
+
+        if !(200..300).contains(&res.status_code()) {
+            return Err(HttpStatusError::new(&self.method, &req.url, &res).into());
+        }
+        Ok(res)
+    }
+
+    /// Sends the request and deserializes a successful response's JSON body.
+    pub fn send_json<T: DeserializeOwned>(self) -> Result<T> {
+        let description = format!("{} {}", self.method, self.url());
+        let res = self.header("Accept", "application/json").send()?;
+        serde_json::from_slice(&res.body())
+            .with_context(|| format!("invalid JSON in response to {description}"))
+    }
+}
+
+/// A response with a non-success status.
+#[derive(Debug, Clone)]
+pub struct HttpStatusError {
+    /// The request method
+    pub method: String,
+
+    /// The request URL
+    pub url: String,
+
+    /// The response status code
+    pub status: u16,
+
+    /// The response body, lossily decoded as UTF-8
+    pub body: String,
+}
+
+impl HttpStatusError {
+    fn new(method: &str, url: &str, res: &HttpResponse) -> Self {
+        HttpStatusError {
+            method: method.to_string(),
+            url: url.to_string(),
+            status: res.status_code(),
+            body: String::from_utf8_lossy(&res.body()).into_owned(),
+        }
+    }
+}
+
+impl fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} returned status {}",
+            self.method, self.url, self.status
+        )?;
+        let body = self.body.trim();
+        if !body.is_empty() {
+            let quoted: String = body.chars().take(MAX_ERROR_BODY_CHARS).collect();
+            let ellipsis = if quoted.len() < body.len() { "…" } else { "" };
+            write!(f, ": {quoted}{ellipsis}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_joined_to_the_base_url() {
+        let client = HttpClient::new().with_base_url("https://api.example.com/v1/");
+        assert_eq!(client.url("issues"), "https://api.example.com/v1/issues");
+        assert_eq!(
+            client.url("/issues/7"),
+            "https://api.example.com/v1/issues/7"
+        );
+        assert_eq!(client.url(""), "https://api.example.com/v1/");
+
+        let client = HttpClient::new().with_base_url("https://api.example.com/v1");
+        assert_eq!(client.url("issues"), "https://api.example.com/v1/issues");
+    }
+
+    #[test]
+    fn absolute_urls_ignore_the_base_url() {
+        let client = HttpClient::new().with_base_url("https://api.example.com/v1");
+        assert_eq!(
+            client.url("https://uploads.example.com/files"),
+            "https://uploads.example.com/files"
+        );
+        assert_eq!(HttpClient::new().url("issues"), "issues");
+    }
+
+    #[test]
+    fn query_parameters_are_percent_encoded() {
+        let client = HttpClient::new().with_base_url("https://api.example.com");
+        let url = client
+            .get("search")
+            .query("q", "rust & wasm")
+            .query_pairs([("page", 2), ("per page", 50)])
+            .query("filter", "a=b/c?d")
+            .url();
+        assert_eq!(
+            url,
+            "https://api.example.com/search?q=rust%20%26%20wasm&page=2&per%20page=50&filter=a%3Db%2Fc%3Fd"
+        );
+        assert_eq!(client.get("search").url(), "https://api.example.com/search");
+    }
+
+    #[test]
+    fn query_parameters_extend_an_existing_query() {
+        let client = HttpClient::new();
+        let url = client
+            .get("https://api.example.com/search?sort=new")
+            .query("q", "é")
+            .url();
+        assert_eq!(url, "https://api.example.com/search?sort=new&q=%C3%A9");
+    }
+
+    fn status_error(body: &str) -> HttpStatusError {
+        HttpStatusError {
+            method: "GET".to_string(),
+            url: "https://api.example.com/issues".to_string(),
+            status: 503,
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn status_errors_quote_the_body() {
+        assert_eq!(
+            status_error("  overloaded\n").to_string(),
+            "GET https://api.example.com/issues returned status 503: overloaded"
+        );
+        assert_eq!(
+            status_error(" \n").to_string(),
+            "GET https://api.example.com/issues returned status 503"
+        );
+    }
+
+    #[test]
+    fn long_bodies_are_truncated() {
+        let prefix = "GET https://api.example.com/issues returned status 503: ";
+        let body = "é".repeat(MAX_ERROR_BODY_CHARS);
+        assert_eq!(status_error(&body).to_string(), format!("{prefix}{body}"));
+
+        let message = status_error(&format!("{body}x")).to_string();
+        assert_eq!(message, format!("{prefix}{body}…"));
+    }
+}
@@ -0,0 +1,223 @@
+#![allow(unused)]
+use super::{imports, types::*};
+use extism_pdk::LogLevel;
+use serde_json::{Map, Value};
+use std::{
+    fmt,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+use tracing_core::{
+    Dispatch, Event, Interest, Metadata,
+    field::{Field, Visit},
+    span,
+};
+
+/// The lowest level the client asked to receive with `logging/setLevel`, if it has.
+static LEVEL: Mutex<Option<LoggingLevel>> = Mutex::new(None);
+
+/// Whether the pdk's logger, rather than one installed by the plugin, receives `log` records.
+static LOGGER_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the pdk's `log` logger and `tracing` subscriber as the global defaults.
+///
+/// Records from either crate are sent to the client with `notify_logging_message`: the
+/// level is mapped to the nearest [`LoggingLevel`], the logger is the module path (or the
+/// target when there is none), and `data` is a JSON object holding the formatted `message`
+/// together with the record's structured fields. Should the host import fail, the message
+/// is written to Extism's own log instead. Messages below the level set by the client are
+/// dropped before reaching the host. A logger or subscriber that the plugin installed first
+/// is left in place.
+pub(crate) fn init() {
+    if log::set_logger(&McpLogger).is_ok() {
+        LOGGER_INSTALLED.store(true, Ordering::Relaxed);
+        log::set_max_level(max_log_level());
+    }
+    let _ = tracing_core::dispatcher::set_global_default(Dispatch::new(McpSubscriber::default()));
+}
+
+/// Sets the lowest level of messages sent to the client, as requested by `logging/setLevel`.
+pub(crate) fn set_level(level: LoggingLevel) {
+    *LEVEL.lock().unwrap_or_else(|e| e.into_inner()) = Some(level);
+    if LOGGER_INSTALLED.load(Ordering::Relaxed) {
+        log::set_max_level(max_log_level());
+    }
+}
+
+/// Returns the lowest level the client asked to receive, or `None` if it has not set one.
+pub fn level() -> Option<LoggingLevel> {
+    *LEVEL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Returns whether messages at `level` are sent to the client.
+pub fn enabled(level: LoggingLevel) -> bool {
+    self::level().is_none_or(|min| level >= min)
+}
+
+fn max_log_level() -> log::LevelFilter {
+    [
+        log::Level::Trace,
+        log::Level::Debug,
+        log::Level::Info,
+        log::Level::Warn,
+        log::Level::Error,
+    ]
+    .into_iter()
+    .find(|level| enabled(log_level(*level)))
+    .map_or(log::LevelFilter::Off, |level| level.to_level_filter())
+}
+
+fn log_level(level: log::Level) -> LoggingLevel {
+    match level {
+        log::Level::Error => LoggingLevel::Error,
+        log::Level::Warn => LoggingLevel::Warning,
+        log::Level::Info => LoggingLevel::Info,
+        log::Level::Debug | log::Level::Trace => LoggingLevel::Debug,
+    }
+}
+
+fn tracing_level(level: &tracing_core::Level) -> LoggingLevel {
+    match *level {
+        tracing_core::Level::ERROR => LoggingLevel::Error,
+        tracing_core::Level::WARN => LoggingLevel::Warning,
+        tracing_core::Level::INFO => LoggingLevel::Info,
+        _ => LoggingLevel::Debug,
+    }
+}
+
+fn send(level: LoggingLevel, logger: &str, data: Map<String, Value>) {
+    let data = Value::Object(data);
+    let sent = imports::notify_logging_message(LoggingMessageNotificationParam {
+        data: data.clone(),
+        level,
+        logger: Some(logger.to_string()),
+    });
+    if sent.is_err() {
+        let level = match level {
+            LoggingLevel::Debug => LogLevel::Debug,
+            LoggingLevel::Info | LoggingLevel::Notice => LogLevel::Info,
+            LoggingLevel::Warning => LogLevel::Warn,
+            _ => LogLevel::Error,
+        };
+        extism_pdk::log!(level, "[{logger}] {data}");
+    }
+}
+
+struct McpLogger;
+
+impl log::Log for McpLogger {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        enabled(log_level(metadata.level()))
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        let level = log_level(record.level());
+        if !enabled(level) {
+            return;
+        }
+        let mut data = Map::new();
+        data.insert(
+            "message".to_string(),
+            Value::String(record.args().to_string()),
+        );
+        let _ = record.key_values().visit(&mut Fields(&mut data));
+        send(level, record.module_path().unwrap_or(record.target()), data);
+    }
+
+    fn flush(&self) {}
+}
+
+/// Collects structured fields of `log` records and `tracing` events as JSON values.
+struct Fields<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        let value = if let Some(value) = value.to_bool() {
+            Value::Bool(value)
+        } else if let Some(value) = value.to_i64() {
+            value.into()
+        } else if let Some(value) = value.to_u64() {
+            value.into()
+        } else if let Some(value) = value.to_f64() {
+            value.into()
+        } else {
+            Value::String(value.to_string())
+        };
+        self.0.insert(key.as_str().to_string(), value);
+        Ok(())
+    }
+}
+
+impl Visit for Fields<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}").into());
+    }
+}
+
+/// Sends `tracing` events to the client; spans are given ids but not reported.
+#[derive(Default)]
+struct McpSubscriber {
+    next_span: AtomicU64,
+}
+
+impl tracing_core::Subscriber for McpSubscriber {
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // The client can change the level at any time, so `enabled` is asked on every event.
+        Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        enabled(tracing_level(metadata.level()))
+    }
+
+    fn new_span(&self, _span: &span::Attributes<'_>) -> span::Id {
+        span::Id::from_u64(self.next_span.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let metadata = event.metadata();
+        let level = tracing_level(metadata.level());
+        let mut data = Map::new();
+        event.record(&mut Fields(&mut data));
+        send(
+            level,
+            metadata.module_path().unwrap_or(metadata.target()),
+            data,
+        );
+    }
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+}
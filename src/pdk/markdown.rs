@@ -0,0 +1,202 @@
+#![allow(unused)]
+use super::{
+    prompt_file::{self, MessageRole},
+    types::*,
+};
+use anyhow::Result;
+
+/// Markdown prompt files embedded from the `prompts/` directory by `build.rs`.
+pub(crate) const EMBEDDED_PROMPTS: &[(&str, &str)] =
+    include!(concat!(env!("OUT_DIR"), "/prompts.rs"));
+
+/// A prompt parsed from a Markdown file.
+#[derive(Debug, Clone)]
+pub struct MarkdownPrompt {
+    /// The prompt definition from the front matter
+    pub prompt: Prompt,
+
+    /// The message templates from the body, in order
+    pub messages: Vec<(Role, String)>,
+}
+
+/// Parses a Markdown prompt.
+///
+/// The file starts with YAML front matter between `---` lines declaring the prompt's
+/// `name`, `title`, `description` and `arguments` (each with `name`, `title`,
+/// `description` and `required`). `name` defaults to `default_name`, typically the file
+/// stem. The body holds the messages: a line `::: user` or `::: assistant` starts a message
+/// in that role, and a line `:::` (or the next role line) ends it. Other `:::` blocks, such
+/// as `::: warning` admonitions, and anything inside fenced code blocks are message text. A
+/// body without role lines is a single user message. Message bodies are prompt templates.
+///
+/// The files embedded from `prompts/` are also checked by `build.rs`, so a broken prompt
+/// fails the build instead of the plugin.
+pub fn parse_prompt(source: &str, default_name: &str) -> Result<MarkdownPrompt> {
+    let (front_matter, body, offset) = prompt_file::parse_front_matter(source)?;
+
+    Ok(MarkdownPrompt {
+        prompt: Prompt {
+            name: front_matter
+                .name
+                .unwrap_or_else(|| default_name.to_string()),
+            title: front_matter.title,
+            description: front_matter.description,
+            arguments: (!front_matter.arguments.is_empty()).then(|| {
+                front_matter
+                    .arguments
+                    .into_iter()
+                    .map(|argument| PromptArgument {
+                        description: argument.description,
+                        name: argument.name,
+                        required: argument.required,
+                        title: argument.title,
+                    })
+                    .collect()
+            }),
+        },
+        messages: prompt_file::parse_messages(body, offset)?
+            .into_iter()
+            .map(|(role, text)| {
+                let role = match role {
+                    MessageRole::User => Role::User,
+                    MessageRole::Assistant => Role::Assistant,
+                };
+                (role, text)
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(body: &str) -> Vec<(&'static str, String)> {
+        parse_prompt(&format!("---\n---\n{body}"), "test")
+            .unwrap()
+            .messages
+            .into_iter()
+            .map(|(role, text)| {
+                let role = match role {
+                    Role::User => "user",
+                    Role::Assistant => "assistant",
+                };
+                (role, text)
+            })
+            .collect()
+    }
+
+    fn error(source: &str) -> String {
+        format!("{:#}", parse_prompt(source, "test").unwrap_err())
+    }
+
+    #[test]
+    fn parses_front_matter() {
+        let parsed = parse_prompt(
+            "---\nname: review\ntitle: Code review\ndescription: Reviews a change\narguments:\n  - name: diff\n    description: The change\n    required: true\n  - name: focus\n---\nReview {{ diff }}\n",
+            "file-stem",
+        )
+        .unwrap();
+        assert_eq!(parsed.prompt.name, "review");
+        assert_eq!(parsed.prompt.title.as_deref(), Some("Code review"));
+        assert_eq!(
+            parsed.prompt.description.as_deref(),
+            Some("Reviews a change")
+        );
+        let arguments = parsed.prompt.arguments.unwrap();
+        assert_eq!(arguments.len(), 2);
+        assert_eq!(arguments[0].name, "diff");
+        assert_eq!(arguments[0].required, Some(true));
+        assert_eq!(arguments[1].name, "focus");
+        assert_eq!(arguments[1].required, None);
+    }
+
+    #[test]
+    fn name_defaults_and_optional_fields_are_absent() {
+        let parsed = parse_prompt("\u{feff}---\r\n---\r\nHello\r\n", "greeting").unwrap();
+        assert_eq!(parsed.prompt.name, "greeting");
+        assert!(parsed.prompt.title.is_none());
+        assert!(parsed.prompt.arguments.is_none());
+        assert_eq!(parsed.messages.len(), 1);
+    }
+
+    #[test]
+    fn rejects_bad_front_matter() {
+        assert!(error("Hello\n").contains("missing front matter"));
+        assert!(error("---\nname: x\nHello\n").contains("unterminated front matter"));
+        assert!(error("---\nnmae: x\n---\nHello\n").contains("unknown field `nmae`"));
+        assert!(error("---\narguments: [\n---\nHello\n").contains("invalid front matter"));
+    }
+
+    #[test]
+    fn body_without_roles_is_one_user_message() {
+        assert_eq!(
+            messages("\nSummarize this.\n\nBriefly.\n"),
+            [("user", "Summarize this.\n\nBriefly.".to_string())]
+        );
+    }
+
+    #[test]
+    fn role_lines_split_messages() {
+        assert_eq!(
+            messages(
+                "::: user\nWhat is 2 + 2?\n:::\n\n::: assistant\n4\n::: user\nAnd 3 + 3?\n:::\n"
+            ),
+            [
+                ("user", "What is 2 + 2?".to_string()),
+                ("assistant", "4".to_string()),
+                ("user", "And 3 + 3?".to_string()),
+            ]
+        );
+        assert_eq!(
+            messages(":::assistant\nHi\n"),
+            [("assistant", "Hi".to_string())]
+        );
+    }
+
+    #[test]
+    fn fenced_code_is_message_text() {
+        let body =
+            "::: user\nRun this:\n```md\n::: assistant\n:::\n```\n~~~~\n```\n::: user\n~~~~\n:::\n";
+        assert_eq!(
+            messages(body),
+            [(
+                "user",
+                "Run this:\n```md\n::: assistant\n:::\n```\n~~~~\n```\n::: user\n~~~~".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn admonitions_are_message_text() {
+        let body = "::: user\nRead this:\n::: warning\nCareful.\n::: note\nNested.\n:::\n:::\nThanks.\n:::\n::: assistant\nOK\n";
+        assert_eq!(
+            messages(body),
+            [
+                (
+                    "user",
+                    "Read this:\n::: warning\nCareful.\n::: note\nNested.\n:::\n:::\nThanks."
+                        .to_string()
+                ),
+                ("assistant", "OK".to_string()),
+            ]
+        );
+        assert_eq!(
+            messages("::: tip\nA tip.\n:::\n"),
+            [("user", "::: tip\nA tip.\n:::".to_string())]
+        );
+    }
+
+    #[test]
+    fn rejects_text_outside_of_messages() {
+        assert!(
+            error("---\n---\n::: user\nHi\n:::\nstray\n")
+                .contains("line 6: text outside of a '::: user' or '::: assistant' block")
+        );
+        assert!(
+            error("---\n---\nintro\n::: user\nHi\n")
+                .contains("line 4: text before the first '::: user' or '::: assistant' block")
+        );
+        assert!(error("---\n---\n\n").contains("prompt has no messages"));
+    }
+}
@@ -1,4 +1,23 @@
+pub mod cancellation;
+pub mod completion;
+pub mod elicitation;
+pub mod error;
 pub mod exports;
 pub mod http;
+pub mod http_client;
 pub mod imports;
+pub mod logging;
+pub mod markdown;
+pub mod oauth;
+pub mod pagination;
+pub mod progress;
+mod prompt_file;
+pub mod prompts;
+pub mod rate_limit;
+pub mod resources;
+pub mod secrets;
+pub mod template;
+pub mod tools;
 pub mod types;
+pub mod uri_template;
+pub mod validation;
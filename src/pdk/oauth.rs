@@ -0,0 +1,665 @@
+#![allow(unused)]
+use super::{
+    http::RetryPolicy,
+    http_client::{HttpClient, HttpStatusError},
+    imports,
+    types::*,
+    uri_template,
+};
+use anyhow::{Context, Result, anyhow};
+use base64::{
+    Engine,
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How long before `expires_at` a cached token is considered expired.
+const EXPIRY_LEEWAY: Duration = Duration::from_secs(30);
+
+/// Default time the user has to sign in before the flow gives up.
+pub const DEFAULT_SIGN_IN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How often the callback relay is asked whether the redirect has arrived.
+const RELAY_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Access tokens obtained by this plugin instance, by [`BearerAuth::cache_key`].
+static TOKENS: Mutex<Option<HashMap<String, AccessToken>>> = Mutex::new(None);
+
+/// Authenticates HTTP requests with OAuth bearer tokens obtained from the host.
+///
+/// Tokens come from [`imports::get_access_token`] and are cached in the plugin, shared by
+/// every `BearerAuth` with the same client, token endpoint and scopes, until shortly before
+/// their `expires_at`. [`HttpClient::with_bearer_auth`](super::http_client::HttpClient::with_bearer_auth)
+/// attaches the token to each request, and on a `401 Unauthorized` response requests a new
+/// token once and retries with it.
+#[derive(Debug, Clone)]
+pub struct BearerAuth {
+    credentials: OauthCredentials,
+}
+
+impl BearerAuth {
+    /// Creates the middleware for the given credentials.
+    pub fn new(credentials: OauthCredentials) -> Self {
+        BearerAuth { credentials }
+    }
+
+    /// Returns a valid access token, from the cache if the cached one has not expired.
+    pub fn token(&self) -> Result<String> {
+        let key = self.cache_key();
+        let tokens = TOKENS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(token) = tokens.as_ref().and_then(|tokens| tokens.get(&key))
+            && !is_expired(token)
+        {
+            return Ok(token.access_token.secret().clone());
+        }
+        drop(tokens);
+        self.refresh()
+    }
+
+    /// Discards any cached token and requests a new one from the host.
+    pub fn refresh(&self) -> Result<String> {
+        let key = self.cache_key();
+        let token = imports::get_access_token(self.credentials.clone())?.ok_or_else(|| {
+            anyhow!(
+                "the host could not obtain an access token from {}",
+                self.credentials.token_endpoint_url.as_str()
+            )
+        })?;
+        let secret = token.access_token.secret().clone();
+        TOKENS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert_with(HashMap::new)
+            .insert(key, token);
+        Ok(secret)
+    }
+
+    /// Returns the `Authorization` header value for a token.
+    pub fn header_value(token: &str) -> String {
+        format!("Bearer {token}")
+    }
+
+    fn cache_key(&self) -> String {
+        let mut scopes: Vec<&str> = self
+            .credentials
+            .scopes
+            .iter()
+            .flatten()
+            .map(|scope| scope.as_str())
+            .collect();
+        scopes.sort_unstable();
+        format!(
+            "{}\n{}\n{}",
+            self.credentials.client_id.as_str(),
+            self.credentials.token_endpoint_url.as_str(),
+            scopes.join(" ")
+        )
+    }
+}
+
+fn is_expired(token: &AccessToken) -> bool {
+    token
+        .expires_at
+        .is_some_and(|expires_at| SystemTime::now() + EXPIRY_LEEWAY >= expires_at)
+}
+
+/// Refresh and access tokens from authorization code flows, by keyring entry.
+static FLOW_TOKENS: Mutex<Option<HashMap<String, FlowTokens>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default)]
+struct FlowTokens {
+    access_token: Option<(String, Option<SystemTime>)>,
+    refresh_token: Option<String>,
+    /// A refresh token the token endpoint rejected, so that it is not read again from the
+    /// keyring, which the plugin cannot clear.
+    rejected_refresh_token: Option<String>,
+}
+
+/// An error response from a token endpoint (RFC 6749 section 5.2).
+#[derive(Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+/// Receives the authorization server's redirect on the plugin's behalf.
+///
+/// A plugin cannot receive the redirect itself, and the code it carries is a credential, so
+/// it must not be pasted back through a form elicitation. Instead `redirect_uri` points at a
+/// relay that stores the query string of each redirect it receives under its `state`
+/// parameter, and answers `GET <code_url>?state=<state>` with `404 Not Found` until that
+/// redirect has arrived, then with `200 OK` and the query string (e.g. `code=…&state=…`) as
+/// the body.
+#[derive(Debug, Clone)]
+pub struct CallbackRelay {
+    redirect_uri: String,
+    code_url: String,
+    timeout: Duration,
+}
+
+impl CallbackRelay {
+    /// Creates a relay that receives redirects at `redirect_uri` and hands out their query
+    /// strings at `code_url`.
+    pub fn new(redirect_uri: impl Into<String>, code_url: impl Into<String>) -> Self {
+        CallbackRelay {
+            redirect_uri: redirect_uri.into(),
+            code_url: code_url.into(),
+            timeout: DEFAULT_SIGN_IN_TIMEOUT,
+        }
+    }
+
+    /// Sets how long the user has to sign in.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Waits until the redirect carrying `state` has arrived, and returns its code.
+    fn wait_for_code(&self, state: &str) -> Result<String> {
+        let client = HttpClient::new();
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            match client.get(&self.code_url).query("state", state).send() {
+                Ok(res) => {
+                    return parse_redirect_query(&String::from_utf8_lossy(&res.body()), state);
+                }
+                Err(e) if is_not_found(&e) => {}
+                Err(e) => {
+                    return Err(e.context("failed to fetch the authorization code from the relay"));
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(anyhow!(
+                    "sign-in timed out after {}s",
+                    self.timeout.as_secs()
+                ));
+            }
+            thread::sleep(RELAY_POLL_INTERVAL);
+        }
+    }
+}
+
+/// The OAuth 2.0 authorization code flow with PKCE (RFC 7636), driven through elicitation.
+///
+/// [`access_token`](Self::access_token) returns a cached access token while it is valid,
+/// then tries the refresh token, and only when there is none (or it is rejected) asks the
+/// user to sign in: a URL elicitation sends them to the authorization endpoint, and the
+/// flow then waits for the [`CallbackRelay`] to receive the redirect. The redirect must
+/// carry the `state` the flow sent, so that a code from another sign-in is never accepted.
+/// The code is exchanged at the token endpoint with the PKCE verifier. Once the user has
+/// accepted the URL elicitation, it is reported complete however the sign-in ends.
+///
+/// A refresh token provisioned in the keyring entry is read with `get_keyring_secret`. The
+/// host offers no way to write the keyring, so refresh tokens obtained by the flow are only
+/// kept in memory for the lifetime of the plugin instance.
+#[derive(Debug, Clone)]
+pub struct AuthorizationCodeFlow {
+    client_id: String,
+    client_secret: Option<String>,
+    auth_type: AuthType,
+    authorize_url: String,
+    token_url: String,
+    relay: CallbackRelay,
+    scopes: Vec<String>,
+    keyring_entry: KeyringEntryId,
+}
+
+impl AuthorizationCodeFlow {
+    /// Creates a flow for a public client; a refresh token is read from the keyring entry
+    /// with the plugin's name as service and `client_id` as user.
+    pub fn new(
+        client_id: impl Into<String>,
+        authorize_url: impl Into<String>,
+        token_url: impl Into<String>,
+        relay: CallbackRelay,
+    ) -> Self {
+        let client_id = client_id.into();
+        AuthorizationCodeFlow {
+            keyring_entry: KeyringEntryId {
+                service: env!("CARGO_PKG_NAME").to_string(),
+                user: client_id.clone(),
+            },
+            client_id,
+            client_secret: None,
+            auth_type: AuthType::RequestBody,
+            authorize_url: authorize_url.into(),
+            token_url: token_url.into(),
+            relay,
+            scopes: Vec::new(),
+        }
+    }
+
+    /// Sets the client secret for confidential clients and how it is sent to the token
+    /// endpoint.
+    pub fn with_client_secret(mut self, secret: impl Into<String>, auth_type: AuthType) -> Self {
+        self.client_secret = Some(secret.into());
+        self.auth_type = auth_type;
+        self
+    }
+
+    /// Sets the scopes to request.
+    pub fn with_scopes<S: Into<String>>(mut self, scopes: impl IntoIterator<Item = S>) -> Self {
+        self.scopes = scopes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the keyring entry that may hold a refresh token.
+    pub fn with_keyring_entry(mut self, entry: KeyringEntryId) -> Self {
+        self.keyring_entry = entry;
+        self
+    }
+
+    /// Returns a valid access token, signing the user in if necessary.
+    ///
+    /// The user is only asked to sign in again when there is no refresh token or the token
+    /// endpoint rejects it with `invalid_grant`. Other refresh failures, such as network
+    /// errors or an unavailable token endpoint, are returned, and the refresh token is kept
+    /// for the next call.
+    pub fn access_token(&self) -> Result<String> {
+        let cached = self.cached();
+        if let Some((token, expires_at)) = &cached.access_token
+            && expires_at.is_none_or(|at| SystemTime::now() + EXPIRY_LEEWAY < at)
+        {
+            return Ok(token.clone());
+        }
+
+        let refresh_token = cached.refresh_token.or_else(|| {
+            self.stored_refresh_token()
+                .filter(|token| cached.rejected_refresh_token.as_ref() != Some(token))
+        });
+        if let Some(refresh_token) = refresh_token {
+            match self.refresh(&refresh_token) {
+                Ok(token) => return Ok(token),
+                Err(e) if is_invalid_grant(&e) => self.forget_refresh_token(refresh_token),
+                Err(e) => return Err(e.context("failed to refresh the access token")),
+            }
+        }
+        self.authorize()
+    }
+
+    /// Signs the user in interactively and returns the new access token.
+    pub fn authorize(&self) -> Result<String> {
+        let verifier = random_token(32);
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+        let state = random_token(16);
+
+        let mut url = self.authorize_url.clone();
+        let mut separator = if url.contains('?') { '&' } else { '?' };
+        for (name, value) in [
+            ("response_type", "code"),
+            ("client_id", &self.client_id),
+            ("redirect_uri", &self.relay.redirect_uri),
+            ("scope", &self.scopes.join(" ")),
+            ("state", &state),
+            ("code_challenge", &challenge),
+            ("code_challenge_method", "S256"),
+        ] {
+            if name == "scope" && value.is_empty() {
+                continue;
+            }
+            url.push(separator);
+            url.push_str(name);
+            url.push('=');
+            uri_template::encode(value, false, &mut url);
+            separator = '&';
+        }
+
+        let elicitation_id = random_token(16);
+        let consent = imports::create_elicitation(ElicitationRequestParamWithTimeout {
+            inner: ElicitationRequestParam::Url {
+                elicitation_id: elicitation_id.clone(),
+                message: "Sign in to authorize access to your account.".to_string(),
+                url,
+            },
+            timeout: None,
+        })?;
+        if !matches!(consent.action, ElicitationResultAction::Accept) {
+            return Err(anyhow!("sign-in was declined"));
+        }
+
+        let _completion = ElicitationCompletion(elicitation_id);
+
+        let code = self.relay.wait_for_code(&state)?;
+        self.request_tokens(&[
+            ("grant_type", "authorization_code"),
+            ("code", &code),
+            ("redirect_uri", &self.relay.redirect_uri),
+            ("code_verifier", &verifier),
+        ])
+    }
+
+    /// Exchanges a refresh token for a new access token.
+    fn refresh(&self, refresh_token: &str) -> Result<String> {
+        self.request_tokens(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+        ])
+    }
+
+    /// Calls the token endpoint and caches the tokens it returns.
+    fn request_tokens(&self, params: &[(&str, &str)]) -> Result<String> {
+        let mut form = vec![("client_id", self.client_id.as_str())];
+        let client = HttpClient::new();
+        let mut request = client
+            .post(&self.token_url)
+            .retry_policy(RetryPolicy::never());
+        match (&self.client_secret, &self.auth_type) {
+            (Some(secret), AuthType::BasicAuth) => {
+                let credentials = STANDARD.encode(format!("{}:{secret}", self.client_id));
+                request = request.header("Authorization", format!("Basic {credentials}"));
+            }
+            (Some(secret), AuthType::RequestBody) => form.push(("client_secret", secret)),
+            (None, _) => {}
+        }
+        form.extend_from_slice(params);
+
+        let mut body = String::new();
+        for (name, value) in form {
+            if !body.is_empty() {
+                body.push('&');
+            }
+            uri_template::encode(name, false, &mut body);
+            body.push('=');
+            uri_template::encode(value, false, &mut body);
+        }
+        let response: TokenResponse = request
+            .body(body, "application/x-www-form-urlencoded")
+            .send_json()
+            .context("token request failed")?;
+
+        let expires_at = response
+            .expires_in
+            .map(|secs| SystemTime::now() + Duration::from_secs(secs));
+        let mut tokens = FLOW_TOKENS.lock().unwrap_or_else(|e| e.into_inner());
+        let cached = tokens
+            .get_or_insert_with(HashMap::new)
+            .entry(self.cache_key())
+            .or_default();
+        cached.access_token = Some((response.access_token.clone(), expires_at));
+        if let Some(refresh_token) = response.refresh_token {
+            cached.refresh_token = Some(refresh_token);
+        }
+        Ok(response.access_token)
+    }
+
+    fn cache_key(&self) -> String {
+        format!(
+            "{}\n{}",
+            self.keyring_entry.service, self.keyring_entry.user
+        )
+    }
+
+    fn cached(&self) -> FlowTokens {
+        FLOW_TOKENS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .and_then(|tokens| tokens.get(&self.cache_key()).cloned())
+            .unwrap_or_default()
+    }
+
+    fn stored_refresh_token(&self) -> Option<String> {
+        let secret = imports::get_keyring_secret(self.keyring_entry.clone()).ok()?;
+        String::from_utf8(secret)
+            .ok()
+            .filter(|token| !token.is_empty())
+    }
+
+    /// Drops a refresh token the token endpoint rejected, along with the access token.
+    fn forget_refresh_token(&self, refresh_token: String) {
+        let mut tokens = FLOW_TOKENS.lock().unwrap_or_else(|e| e.into_inner());
+        let cached = tokens
+            .get_or_insert_with(HashMap::new)
+            .entry(self.cache_key())
+            .or_default();
+        cached.access_token = None;
+        if cached.refresh_token.as_ref() == Some(&refresh_token) {
+            cached.refresh_token = None;
+        }
+        cached.rejected_refresh_token = Some(refresh_token);
+    }
+}
+
+/// Reports a URL elicitation complete when dropped, however the sign-in ended.
+struct ElicitationCompletion(String);
+
+impl Drop for ElicitationCompletion {
+    fn drop(&mut self) {
+        let _ = imports::notify_url_elicitation_completed(ElicitationResponseNotificationParam {
+            elicitation_id: self.0.clone(),
+        });
+    }
+}
+
+/// Returns whether a request failed with `404 Not Found`.
+fn is_not_found(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<HttpStatusError>())
+        .any(|e| e.status == 404)
+}
+
+/// Returns whether the token endpoint rejected the grant as invalid, expired or revoked,
+/// rather than failing for a reason that may go away.
+fn is_invalid_grant(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<HttpStatusError>())
+        .any(|e| {
+            matches!(e.status, 400 | 401)
+                && serde_json::from_str::<TokenErrorResponse>(&e.body)
+                    .is_ok_and(|response| response.error == "invalid_grant")
+        })
+}
+
+/// Extracts the authorization code from the query string of a redirect, which must carry
+/// the `state` parameter the flow sent.
+fn parse_redirect_query(query: &str, state: &str) -> Result<String> {
+    let query = query.trim();
+    let query = query.strip_prefix('?').unwrap_or(query);
+    let params: HashMap<String, String> = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| (percent_decode(name), percent_decode(value)))
+        .collect();
+
+    if let Some(error) = params.get("error") {
+        return Err(anyhow!(
+            "authorization failed: {error}{}",
+            params
+                .get("error_description")
+                .map(|d| format!(" ({d})"))
+                .unwrap_or_default()
+        ));
+    }
+    match params.get("state") {
+        Some(s) if s == state => {}
+        Some(_) => {
+            return Err(anyhow!(
+                "authorization failed: the state parameter does not match"
+            ));
+        }
+        None => {
+            return Err(anyhow!(
+                "authorization failed: the redirect has no state parameter"
+            ));
+        }
+    }
+    params
+        .get("code")
+        .cloned()
+        .ok_or_else(|| anyhow!("the redirect has no authorization code"))
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                out.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Returns `len` random bytes encoded as unpadded base64url.
+fn random_token(len: usize) -> String {
+    let mut bytes = vec![0; len];
+    getrandom::getrandom(&mut bytes).expect("the host provides randomness");
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status_error(status: u16, body: &str) -> anyhow::Error {
+        anyhow::Error::new(HttpStatusError {
+            method: "POST".to_string(),
+            url: "https://auth.example.com/token".to_string(),
+            status,
+            body: body.to_string(),
+        })
+    }
+
+    #[test]
+    fn redirect_yields_the_code_when_the_state_matches() {
+        let code = parse_redirect_query("code=a%2Fb+c&state=xyz\n", "xyz").unwrap();
+        assert_eq!(code, "a/b c");
+        let code = parse_redirect_query("?state=xyz&code=123", "xyz").unwrap();
+        assert_eq!(code, "123");
+    }
+
+    #[test]
+    fn redirect_requires_the_state_it_was_sent() {
+        let error = |query: &str| parse_redirect_query(query, "xyz").unwrap_err().to_string();
+        assert!(error("code=123&state=abc").contains("does not match"));
+        assert!(error("code=123").contains("no state parameter"));
+        assert!(error("code=123&state=").contains("does not match"));
+        assert!(error("state=xyz").contains("no authorization code"));
+        assert!(error("").contains("no state parameter"));
+    }
+
+    #[test]
+    fn redirect_reports_authorization_errors() {
+        let error = parse_redirect_query(
+            "error=access_denied&error_description=User+said+no&state=xyz",
+            "xyz",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "authorization failed: access_denied (User said no)"
+        );
+    }
+
+    #[test]
+    fn relay_answers_not_found_until_the_redirect_arrives() {
+        assert!(is_not_found(
+            &status_error(404, "").context("GET https://relay.example.com failed")
+        ));
+        assert!(!is_not_found(&status_error(500, "")));
+        assert!(!is_not_found(&anyhow!("connection refused")));
+    }
+
+    #[test]
+    fn only_invalid_grant_responses_are_invalid_grants() {
+        assert!(is_invalid_grant(&status_error(
+            400,
+            r#"{"error": "invalid_grant", "error_description": "revoked"}"#
+        )));
+        assert!(is_invalid_grant(
+            &status_error(401, r#"{"error":"invalid_grant"}"#).context("token request failed")
+        ));
+        assert!(!is_invalid_grant(&status_error(
+            400,
+            r#"{"error":"invalid_client"}"#
+        )));
+        assert!(!is_invalid_grant(&status_error(
+            503,
+            r#"{"error":"invalid_grant"}"#
+        )));
+        assert!(!is_invalid_grant(&status_error(400, "Bad Request")));
+        assert!(!is_invalid_grant(&anyhow!("connection reset")));
+    }
+
+    #[test]
+    fn rejected_refresh_tokens_are_forgotten() {
+        let flow = AuthorizationCodeFlow::new(
+            "forget-test",
+            "https://auth.example.com/authorize",
+            "https://auth.example.com/token",
+            CallbackRelay::new(
+                "https://relay.example.com/callback",
+                "https://relay.example.com/code",
+            ),
+        );
+        FLOW_TOKENS
+            .lock()
+            .unwrap()
+            .get_or_insert_with(HashMap::new)
+            .insert(
+                flow.cache_key(),
+                FlowTokens {
+                    access_token: Some(("expired".to_string(), Some(SystemTime::UNIX_EPOCH))),
+                    refresh_token: Some("refresh".to_string()),
+                    rejected_refresh_token: None,
+                },
+            );
+
+        flow.forget_refresh_token("refresh".to_string());
+        let cached = flow.cached();
+        assert!(cached.access_token.is_none());
+        assert!(cached.refresh_token.is_none());
+        assert_eq!(cached.rejected_refresh_token.as_deref(), Some("refresh"));
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("a%20b+c%2B%zz"), "a b c+%zz");
+        assert_eq!(percent_decode("%E2%9C%93"), "\u{2713}");
+    }
+
+    #[test]
+    fn random_tokens_are_unpadded_base64url() {
+        let token = random_token(32);
+        assert_eq!(token.len(), 43);
+        assert!(
+            token
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        );
+        assert_ne!(token, random_token(32));
+    }
+}
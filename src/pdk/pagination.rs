@@ -0,0 +1,261 @@
+#![allow(unused)]
+use super::error::McpError;
+use anyhow::Result;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::OnceLock;
+
+/// Number of items returned per page unless configured otherwise.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Number of bytes of the HMAC tag kept in each cursor.
+const TAG_LEN: usize = 16;
+
+/// Number of bytes of the random key generated for each plugin instance.
+const KEY_LEN: usize = 32;
+
+/// One page of a paginated list.
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    /// The items on this page
+    pub items: Vec<T>,
+
+    /// Cursor for the next page, or `None` if this is the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Slices full lists into pages addressed by opaque cursors.
+///
+/// A cursor encodes the offset of the next page together with an HMAC over the offset and
+/// the list's scope (e.g. `"tools"`), so clients cannot forge or edit cursors, nor replay a
+/// cursor from one list against another. The key is random and generated once per plugin
+/// instance, so cursors are only accepted by the instance that issued them; plugins that run
+/// several instances behind one client can share a secret key with
+/// [`with_key`](Paginator::with_key), for example one read from their config.
+#[derive(Debug, Clone)]
+pub struct Paginator {
+    page_size: usize,
+    key: Vec<u8>,
+}
+
+impl Default for Paginator {
+    fn default() -> Self {
+        Paginator {
+            page_size: DEFAULT_PAGE_SIZE,
+            key: instance_key().to_vec(),
+        }
+    }
+}
+
+impl Paginator {
+    /// Sets the maximum number of items per page (at least 1).
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    /// Sets the key used to sign cursors, which must be kept secret.
+    pub fn with_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    /// Returns the maximum number of items per page.
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Returns the page of `items` starting at `cursor`, or the first page if there is none.
+    ///
+    /// Fails if the cursor is malformed, was issued for a different scope or key, or points
+    /// past the end of the list.
+    pub fn paginate<T: Clone>(
+        &self,
+        scope: &str,
+        items: &[T],
+        cursor: Option<&str>,
+    ) -> Result<Page<T>> {
+        let start = match cursor {
+            Some(cursor) => self.decode(scope, cursor)?,
+            None => 0,
+        };
+        if start > items.len() {
+            return Err(invalid_cursor(&format!("past the end of the {scope} list")));
+        }
+
+        let end = start.saturating_add(self.page_size).min(items.len());
+        Ok(Page {
+            items: items[start..end].to_vec(),
+            next_cursor: (end < items.len()).then(|| self.encode(scope, end)),
+        })
+    }
+
+    fn mac(&self, scope: &str, offset: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(scope.as_bytes());
+        mac.update(&[0]);
+        mac.update(offset);
+        mac
+    }
+
+    fn encode(&self, scope: &str, offset: usize) -> String {
+        let offset = (offset as u64).to_be_bytes();
+        let mut raw = offset.to_vec();
+        raw.extend_from_slice(&self.mac(scope, &offset).finalize().into_bytes()[..TAG_LEN]);
+        URL_SAFE_NO_PAD.encode(raw)
+    }
+
+    fn decode(&self, scope: &str, cursor: &str) -> Result<usize> {
+        let raw = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .map_err(|_| invalid_cursor("not a cursor issued by this plugin"))?;
+        if raw.len() != 8 + TAG_LEN {
+            return Err(invalid_cursor("not a cursor issued by this plugin"));
+        }
+        let (offset, tag) = raw.split_at(8);
+
+        self.mac(scope, offset)
+            .verify_truncated_left(tag)
+            .map_err(|_| invalid_cursor(&format!("not a cursor issued for the {scope} list")))?;
+
+        let offset = u64::from_be_bytes(offset.try_into().expect("split at 8 bytes"));
+        usize::try_from(offset).map_err(|_| invalid_cursor("offset out of range"))
+    }
+}
+
+fn invalid_cursor(reason: &str) -> anyhow::Error {
+    McpError::invalid_params(format!("invalid cursor: {reason}")).into()
+}
+
+/// Returns the random key that signs cursors for this plugin instance.
+fn instance_key() -> &'static [u8; KEY_LEN] {
+    static KEY: OnceLock<[u8; KEY_LEN]> = OnceLock::new();
+    KEY.get_or_init(|| {
+        let mut key = [0; KEY_LEN];
+        getrandom::getrandom(&mut key).expect("the host provides randomness");
+        key
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdk::error::McpError;
+
+    fn items() -> Vec<u32> {
+        (0..25).collect()
+    }
+
+    fn assert_invalid(result: Result<Page<u32>>) {
+        let e = result.unwrap_err();
+        let error = e.downcast_ref::<McpError>().expect("an McpError");
+        assert_eq!(error.code, McpError::INVALID_PARAMS);
+        assert!(
+            error.message.starts_with("invalid cursor"),
+            "{}",
+            error.message
+        );
+    }
+
+    #[test]
+    fn round_trip_walks_every_page() {
+        let paginator = Paginator::default().with_page_size(10);
+        let items = items();
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = paginator
+                .paginate("tools", &items, cursor.as_deref())
+                .unwrap();
+            seen.extend(page.items);
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(seen, items);
+    }
+
+    #[test]
+    fn last_page_has_no_cursor() {
+        let page = Paginator::default()
+            .paginate("tools", &[1, 2, 3], None)
+            .unwrap();
+        assert_eq!(page.items, [1, 2, 3]);
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn tampered_cursor_is_rejected() {
+        let paginator = Paginator::default().with_page_size(10);
+        let cursor = paginator
+            .paginate("tools", &items(), None)
+            .unwrap()
+            .next_cursor
+            .unwrap();
+
+        let mut raw = URL_SAFE_NO_PAD.decode(&cursor).unwrap();
+        raw[7] = 20;
+        assert_invalid(paginator.paginate("tools", &items(), Some(&URL_SAFE_NO_PAD.encode(&raw))));
+
+        let mut raw = URL_SAFE_NO_PAD.decode(&cursor).unwrap();
+        *raw.last_mut().unwrap() ^= 1;
+        assert_invalid(paginator.paginate("tools", &items(), Some(&URL_SAFE_NO_PAD.encode(&raw))));
+
+        assert_invalid(paginator.paginate("tools", &items(), Some("not a cursor")));
+        assert_invalid(paginator.paginate("tools", &items(), Some(&cursor[..cursor.len() - 2])));
+    }
+
+    #[test]
+    fn cursor_from_another_key_or_scope_is_rejected() {
+        let ours = Paginator::default().with_page_size(10);
+        let theirs = Paginator::default()
+            .with_page_size(10)
+            .with_key("another key");
+        let cursor = theirs
+            .paginate("tools", &items(), None)
+            .unwrap()
+            .next_cursor
+            .unwrap();
+        assert_invalid(ours.paginate("tools", &items(), Some(&cursor)));
+        assert_eq!(
+            theirs
+                .paginate("tools", &items(), Some(&cursor))
+                .unwrap()
+                .items[0],
+            10
+        );
+
+        let cursor = ours
+            .paginate("tools", &items(), None)
+            .unwrap()
+            .next_cursor
+            .unwrap();
+        assert_invalid(ours.paginate("prompts", &items(), Some(&cursor)));
+    }
+
+    #[test]
+    fn default_key_is_not_derived_from_the_package() {
+        let public = concat!(env!("CARGO_PKG_NAME"), "@", env!("CARGO_PKG_VERSION"));
+        let forger = Paginator::default().with_page_size(10).with_key(public);
+        let forged = forger
+            .paginate("tools", &items(), None)
+            .unwrap()
+            .next_cursor
+            .unwrap();
+        assert_invalid(Paginator::default().with_page_size(10).paginate(
+            "tools",
+            &items(),
+            Some(&forged),
+        ));
+    }
+
+    #[test]
+    fn cursor_past_the_end_is_rejected() {
+        let paginator = Paginator::default().with_page_size(10);
+        let cursor = paginator.encode("tools", 26);
+        assert_invalid(paginator.paginate("tools", &items(), Some(&cursor)));
+    }
+}
@@ -0,0 +1,338 @@
+#![allow(unused)]
+use super::{imports, types::*};
+use serde_json::Value;
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Default minimum time between two progress notifications.
+pub const DEFAULT_MIN_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Reports the progress of a request to the client.
+///
+/// Created from the request's context, it sends `notifications/progress` for the
+/// `progressToken` the client attached to the request, and does nothing if there is none.
+/// Reports never go backwards: a position at or below the last one sent is not reported.
+/// Notifications are at least [`DEFAULT_MIN_INTERVAL`] apart (see
+/// [`with_min_interval`](Progress::with_min_interval)); reports in between are skipped,
+/// except the one reaching the total. Notifications are best effort, and a failure to send
+/// one is ignored.
+///
+/// Part of the work can be handed to a [`subtask`](Progress::subtask) with its own total,
+/// which is scaled into the share of its parent it was given:
+///
+/// ```ignore
+/// let mut progress = Progress::new(&input.context).with_total(pages.len() as f64);
+/// for page in pages {
+///     let mut items = progress.subtask(1.0).with_total(page.items.len() as f64);
+///     for item in page.items {
+///         process(item)?;
+///         items.advance(1.0);
+///     }
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Progress {
+    reporter: Option<Arc<Mutex<Reporter>>>,
+    root: bool,
+    start: f64,
+    share: f64,
+    total: f64,
+    done: f64,
+}
+
+type Sink = Box<dyn FnMut(ProgressNotificationParam) + Send>;
+
+struct Reporter {
+    token: ProgressToken,
+    total: f64,
+    min_interval: Duration,
+    sent: f64,
+    sent_at: Option<Instant>,
+    message: Option<String>,
+    sink: Sink,
+}
+
+impl fmt::Debug for Reporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reporter")
+            .field("token", &self.token)
+            .field("total", &self.total)
+            .field("sent", &self.sent)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Progress {
+    /// Creates a handle for the request with `context`, out of a total of 1.
+    pub fn new(context: &PluginRequestContext) -> Self {
+        Progress::with_sink(context, |notification| {
+            let _ = imports::notify_progress(notification);
+        })
+    }
+
+    /// Creates a handle like [`new`](Progress::new) that hands its notifications to `sink`
+    /// instead of sending them.
+    fn with_sink<F>(context: &PluginRequestContext, sink: F) -> Self
+    where
+        F: FnMut(ProgressNotificationParam) + Send + 'static,
+    {
+        let token = match context.meta.get("progressToken") {
+            Some(Value::String(token)) => Some(ProgressToken::String(token.clone())),
+            Some(Value::Number(token)) => token.as_i64().map(ProgressToken::Number),
+            _ => None,
+        };
+        Progress {
+            reporter: token.map(|token| {
+                Arc::new(Mutex::new(Reporter {
+                    token,
+                    total: 1.0,
+                    min_interval: DEFAULT_MIN_INTERVAL,
+                    sent: 0.0,
+                    sent_at: None,
+                    message: None,
+                    sink: Box::new(sink),
+                }))
+            }),
+            root: true,
+            start: 0.0,
+            share: 1.0,
+            total: 1.0,
+            done: 0.0,
+        }
+    }
+
+    /// Sets the total amount of work counted by this handle.
+    ///
+    /// For the handle created by [`new`](Progress::new) this is also the total reported to
+    /// the client; for a sub-task it only changes the units the sub-task counts in.
+    pub fn with_total(mut self, total: f64) -> Self {
+        let total = total.max(0.0);
+        if self.root {
+            self.share = total;
+            if let Some(reporter) = &self.reporter {
+                reporter.lock().unwrap_or_else(|e| e.into_inner()).total = total;
+            }
+        }
+        self.total = total;
+        self
+    }
+
+    /// Sets the minimum time between two notifications for the whole request.
+    pub fn with_min_interval(self, interval: Duration) -> Self {
+        if let Some(reporter) = &self.reporter {
+            reporter
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .min_interval = interval;
+        }
+        self
+    }
+
+    /// Returns whether the client asked for progress notifications.
+    pub fn is_active(&self) -> bool {
+        self.reporter.is_some()
+    }
+
+    /// Sets the message sent with the next notification.
+    pub fn set_message(&mut self, message: impl Into<String>) {
+        if let Some(reporter) = &self.reporter {
+            reporter.lock().unwrap_or_else(|e| e.into_inner()).message = Some(message.into());
+        }
+    }
+
+    /// Reports that `done` units of work are complete.
+    pub fn set(&mut self, done: f64) {
+        self.done = done.min(self.total).max(self.done);
+        self.report();
+    }
+
+    /// Reports that `amount` more units of work are complete.
+    pub fn advance(&mut self, amount: f64) {
+        self.set(self.done + amount);
+    }
+
+    /// Reports that all work is complete.
+    pub fn finish(&mut self) {
+        self.set(self.total);
+    }
+
+    /// Hands the next `weight` units of this handle's work to a sub-task.
+    ///
+    /// The sub-task counts out of a total of 1 until changed with
+    /// [`with_total`](Progress::with_total). This handle moves past the weight right away,
+    /// without reporting it, so that its next report follows the sub-task's work.
+    pub fn subtask(&mut self, weight: f64) -> Progress {
+        let weight = weight.clamp(0.0, self.total - self.done);
+        let subtask = Progress {
+            reporter: self.reporter.clone(),
+            root: false,
+            start: self.position(),
+            share: weight * self.scale(),
+            total: 1.0,
+            done: 0.0,
+        };
+        self.done += weight;
+        subtask
+    }
+
+    fn scale(&self) -> f64 {
+        if self.total > 0.0 {
+            self.share / self.total
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the position of this handle in the units reported to the client.
+    fn position(&self) -> f64 {
+        self.start + self.done * self.scale()
+    }
+
+    fn report(&self) {
+        let Some(reporter) = &self.reporter else {
+            return;
+        };
+        let mut reporter = reporter.lock().unwrap_or_else(|e| e.into_inner());
+        let progress = self.position().min(reporter.total);
+        if progress <= reporter.sent {
+            return;
+        }
+        let now = Instant::now();
+        if progress < reporter.total
+            && reporter
+                .sent_at
+                .is_some_and(|sent_at| now.duration_since(sent_at) < reporter.min_interval)
+        {
+            return;
+        }
+
+        let notification = ProgressNotificationParam {
+            message: reporter.message.take(),
+            progress,
+            progress_token: reporter.token.clone(),
+            total: Some(reporter.total),
+        };
+        (reporter.sink)(notification);
+        reporter.sent = progress;
+        reporter.sent_at = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    type Sent = Arc<Mutex<Vec<ProgressNotificationParam>>>;
+
+    fn context(token: Value) -> PluginRequestContext {
+        PluginRequestContext {
+            meta: json!({ "progressToken": token })
+                .as_object()
+                .cloned()
+                .unwrap(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a handle for a request with `token` that records what it sends.
+    fn progress(token: Value) -> (Progress, Sent) {
+        let sent = Sent::default();
+        let sink = sent.clone();
+        let progress = Progress::with_sink(&context(token), move |notification| {
+            sink.lock().unwrap().push(notification)
+        });
+        (progress, sent)
+    }
+
+    fn positions(sent: &Sent) -> Vec<f64> {
+        sent.lock().unwrap().iter().map(|n| n.progress).collect()
+    }
+
+    #[test]
+    fn requests_without_a_token_report_nothing() {
+        let sent = Sent::default();
+        let sink = sent.clone();
+        let mut progress = Progress::with_sink(&PluginRequestContext::default(), move |n| {
+            sink.lock().unwrap().push(n)
+        });
+        assert!(!progress.is_active());
+        progress.finish();
+        assert!(sent.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn notifications_carry_the_token_total_and_message() {
+        let (progress, sent) = progress(json!(7));
+        let mut progress = progress.with_total(4.0);
+        assert!(progress.is_active());
+        progress.set_message("fetching");
+        progress.advance(1.0);
+        progress.finish();
+
+        let sent = sent.lock().unwrap();
+        assert!(matches!(sent[0].progress_token, ProgressToken::Number(7)));
+        assert_eq!(sent[0].total, Some(4.0));
+        assert_eq!(sent[0].message.as_deref(), Some("fetching"));
+        assert_eq!(sent[1].progress, 4.0);
+        assert_eq!(sent[1].message, None);
+    }
+
+    #[test]
+    fn reports_never_go_backwards() {
+        let (progress, sent) = progress(json!("token"));
+        let mut progress = progress.with_total(10.0).with_min_interval(Duration::ZERO);
+        progress.set(5.0);
+        progress.set(3.0);
+        progress.set(5.0);
+        progress.advance(1.0);
+        progress.set(20.0);
+        assert_eq!(positions(&sent), [5.0, 6.0, 10.0]);
+    }
+
+    #[test]
+    fn reports_are_throttled_except_the_last() {
+        let (progress, sent) = progress(json!("token"));
+        let mut progress = progress
+            .with_total(10.0)
+            .with_min_interval(Duration::from_secs(60));
+        progress.set(1.0);
+        progress.set(2.0);
+        progress.set(9.0);
+        progress.finish();
+        assert_eq!(positions(&sent), [1.0, 10.0]);
+    }
+
+    #[test]
+    fn subtasks_report_within_their_share() {
+        let (progress, sent) = progress(json!("token"));
+        let mut progress = progress.with_total(10.0).with_min_interval(Duration::ZERO);
+        progress.advance(2.0);
+
+        let mut pages = progress.subtask(4.0).with_total(2.0);
+        pages.advance(1.0);
+        let mut items = pages.subtask(1.0).with_total(4.0);
+        items.advance(1.0);
+        items.finish();
+        pages.finish();
+
+        // The parent moved past the subtask's weight without reporting it.
+        progress.advance(1.0);
+        progress.finish();
+        assert_eq!(positions(&sent), [2.0, 4.0, 4.5, 6.0, 7.0, 10.0]);
+    }
+
+    #[test]
+    fn subtask_weights_are_capped_by_the_work_left() {
+        let (progress, sent) = progress(json!("token"));
+        let mut progress = progress.with_total(4.0).with_min_interval(Duration::ZERO);
+        progress.set(3.0);
+        let mut rest = progress.subtask(5.0);
+        rest.set(0.5);
+        rest.finish();
+        assert_eq!(positions(&sent), [3.0, 3.5, 4.0]);
+    }
+}
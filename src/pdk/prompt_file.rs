@@ -0,0 +1,165 @@
+#![allow(unused)]
+// `build.rs` includes this file to check the prompts in `prompts/` at build time, so it
+// must not depend on anything but `std`, `anyhow`, `serde` and `serde_yaml_ng`.
+use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+
+/// The front matter of a Markdown prompt file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FrontMatter {
+    pub(crate) name: Option<String>,
+    pub(crate) title: Option<String>,
+    pub(crate) description: Option<String>,
+    #[serde(default)]
+    pub(crate) arguments: Vec<ArgumentFrontMatter>,
+}
+
+/// An argument declared in the front matter of a Markdown prompt file.
+#[derive(Debug, Deserialize)]
+pub(crate) struct ArgumentFrontMatter {
+    pub(crate) name: String,
+    pub(crate) title: Option<String>,
+    pub(crate) description: Option<String>,
+    pub(crate) required: Option<bool>,
+}
+
+/// The role of a message in a Markdown prompt file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MessageRole {
+    User,
+    Assistant,
+}
+
+/// Splits a Markdown prompt file into its parsed front matter and its body, along with the
+/// number of lines before the body.
+pub(crate) fn parse_front_matter(source: &str) -> Result<(FrontMatter, &str, usize)> {
+    let (front_matter, body) = split_front_matter(source)?;
+    let front_matter = serde_yaml_ng::from_str(front_matter).context("invalid front matter")?;
+    let offset = source[..source.len() - body.len()].matches('\n').count();
+    Ok((front_matter, body, offset))
+}
+
+fn split_front_matter(source: &str) -> Result<(&str, &str)> {
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    let rest = source
+        .strip_prefix("---\n")
+        .or_else(|| source.strip_prefix("---\r\n"))
+        .ok_or_else(|| anyhow!("missing front matter: the file must start with a '---' line"))?;
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return Ok((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    Err(anyhow!(
+        "unterminated front matter: missing closing '---' line"
+    ))
+}
+
+/// Splits the body of a Markdown prompt file into messages.
+///
+/// A line `::: user` or `::: assistant` starts a message in that role, and a line `:::` (or
+/// the next role line) ends it. Other `:::` blocks, such as `::: warning` admonitions, and
+/// anything inside fenced code blocks are part of the message text. A body without role
+/// lines is a single user message.
+///
+/// Line numbers in errors count the `offset` lines before the body, so that they point into
+/// the whole file.
+pub(crate) fn parse_messages(body: &str, offset: usize) -> Result<Vec<(MessageRole, String)>> {
+    let mut messages = Vec::new();
+    let mut current: Option<(MessageRole, String)> = None;
+    let mut saw_role = false;
+    let mut fenced: Option<(char, usize)> = None;
+    let mut admonitions = 0;
+
+    for (number, line) in body.split_inclusive('\n').enumerate() {
+        let trimmed = line.trim();
+        let delimiter = if let Some(open) = fenced {
+            if fence(trimmed).is_some_and(|close| closes(open, close, trimmed)) {
+                fenced = None;
+            }
+            None
+        } else if let Some(open) = fence(trimmed) {
+            fenced = Some(open);
+            None
+        } else {
+            delimiter(trimmed, &mut admonitions)
+        };
+
+        let Some(role) = delimiter else {
+            match &mut current {
+                Some((_, text)) => text.push_str(line),
+                None if trimmed.is_empty() => {}
+                None if saw_role => {
+                    return Err(anyhow!(
+                        "line {}: text outside of a '::: user' or '::: assistant' block",
+                        offset + number + 1
+                    ));
+                }
+                None => current = Some((MessageRole::User, line.to_string())),
+            }
+            continue;
+        };
+
+        if let Some((role, text)) = current.take()
+            && (saw_role || !text.trim().is_empty())
+        {
+            messages.push((role, text));
+        }
+        if !saw_role && !messages.is_empty() {
+            return Err(anyhow!(
+                "line {}: text before the first '::: user' or '::: assistant' block",
+                offset + number + 1
+            ));
+        }
+        saw_role = true;
+        admonitions = 0;
+        current = role.map(|role| (role, String::new()));
+    }
+    messages.extend(current.filter(|(_, text)| saw_role || !text.trim().is_empty()));
+
+    for (_, text) in &mut messages {
+        *text = text.trim().to_string();
+    }
+    if messages.is_empty() {
+        return Err(anyhow!("prompt has no messages"));
+    }
+    Ok(messages)
+}
+
+/// Returns the character and length of the fence that `line` starts with, if any.
+fn fence(line: &str) -> Option<(char, usize)> {
+    let c = line.chars().next().filter(|c| matches!(c, '`' | '~'))?;
+    let len = line.len() - line.trim_start_matches(c).len();
+    (len >= 3).then_some((c, len))
+}
+
+/// Returns whether the fence `close` on `line` closes the code block opened by `open`.
+fn closes(open: (char, usize), close: (char, usize), line: &str) -> bool {
+    close.0 == open.0 && close.1 >= open.1 && line[close.1..].trim().is_empty()
+}
+
+/// Interprets a `:::` line: `Some(Some(role))` starts a message, `Some(None)` ends one, and
+/// `None` is message text, such as an admonition or the `:::` line that closes it.
+fn delimiter(line: &str, admonitions: &mut usize) -> Option<Option<MessageRole>> {
+    let colons = line.len() - line.trim_start_matches(':').len();
+    if colons < 3 {
+        return None;
+    }
+    match line[colons..].trim() {
+        "" if *admonitions > 0 => {
+            *admonitions -= 1;
+            None
+        }
+        "" => Some(None),
+        "user" if colons == 3 => Some(Some(MessageRole::User)),
+        "assistant" if colons == 3 => Some(Some(MessageRole::Assistant)),
+        _ => {
+            *admonitions += 1;
+            None
+        }
+    }
+}
@@ -0,0 +1,386 @@
+#![allow(unused)]
+use super::{
+    completion::Completion, error::McpError, markdown, pagination::Paginator, template::Template,
+    types::*,
+};
+use anyhow::{Result, anyhow};
+use std::{collections::HashMap, sync::OnceLock};
+
+type PromptHandler = Box<dyn Fn(GetPromptRequest) -> Result<GetPromptResult> + Send + Sync>;
+
+/// A set of prompts and the handlers that render them.
+///
+/// Plugins register each prompt once in `register_prompts` in `lib.rs`, declaring its
+/// `PromptArgument`s on the `Prompt` itself; the `list_prompts` and `get_prompt` exports
+/// are then answered from the registry. Required arguments are enforced before a handler
+/// runs, so handlers can rely on them being present, and arguments with an attached
+/// [`Completion`] are completed by the `complete` export.
+#[derive(Default)]
+pub struct PromptRegistry {
+    prompts: Vec<(Prompt, PromptHandler)>,
+    index: HashMap<String, usize>,
+    completions: HashMap<(String, String), Completion>,
+    paginator: Paginator,
+}
+
+impl PromptRegistry {
+    /// Registers a prompt together with the handler that renders it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a prompt with the same name has already been registered.
+    pub fn register<F>(&mut self, prompt: Prompt, handler: F) -> &mut Self
+    where
+        F: Fn(GetPromptRequest) -> Result<GetPromptResult> + Send + Sync + 'static,
+    {
+        if self.index.contains_key(&prompt.name) {
+            panic!("prompt '{}' is already registered", prompt.name);
+        }
+        self.index.insert(prompt.name.clone(), self.prompts.len());
+        self.prompts.push((prompt, Box::new(handler)));
+        self
+    }
+
+    /// Registers a prompt whose messages are rendered from [`Template`]s.
+    ///
+    /// Each message is a role and a template source; the templates see the request's
+    /// arguments as variables. The prompt's description is returned with every result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a template fails to parse, if a template uses a variable that is not a
+    /// declared argument, or if a prompt with the same name has already been registered.
+    pub fn register_template<S>(
+        &mut self,
+        prompt: Prompt,
+        messages: impl IntoIterator<Item = (Role, S)>,
+    ) -> &mut Self
+    where
+        S: AsRef<str>,
+    {
+        let declared: Vec<&str> = prompt
+            .arguments
+            .iter()
+            .flatten()
+            .map(|a| a.name.as_str())
+            .collect();
+        let messages: Vec<(Role, Template)> = messages
+            .into_iter()
+            .map(|(role, source)| {
+                let template = Template::parse(source.as_ref()).unwrap_or_else(|e| {
+                    panic!("invalid template for prompt '{}': {e}", prompt.name)
+                });
+                if let Some(name) = template
+                    .variables()
+                    .into_iter()
+                    .find(|name| !declared.contains(&name.as_str()))
+                {
+                    panic!(
+                        "template for prompt '{}' uses undeclared argument '{name}'",
+                        prompt.name
+                    );
+                }
+                (role, template)
+            })
+            .collect();
+
+        let description = prompt.description.clone();
+        self.register(prompt, move |input| {
+            let arguments = input.request.arguments.unwrap_or_default();
+            Ok(GetPromptResult {
+                description: description.clone(),
+                messages: messages
+                    .iter()
+                    .map(|(role, template)| PromptMessage {
+                        role: role.clone(),
+                        content: ContentBlock::Text(TextContent {
+                            text: template.render(&arguments),
+                            ..Default::default()
+                        }),
+                    })
+                    .collect(),
+            })
+        })
+    }
+
+    /// Registers a prompt written as Markdown with front matter; see [`markdown::parse_prompt`].
+    ///
+    /// `file_name` is used in error messages and, minus its extension, as the default name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the file cannot be parsed, or for any reason [`Self::register_template`]
+    /// would.
+    pub fn register_markdown(&mut self, file_name: &str, source: &str) -> &mut Self {
+        let stem = file_name.strip_suffix(".md").unwrap_or(file_name);
+        let parsed = markdown::parse_prompt(source, stem)
+            .unwrap_or_else(|e| panic!("invalid prompt file '{file_name}': {e:#}"));
+        self.register_template(parsed.prompt, parsed.messages)
+    }
+
+    /// Attaches a completion source to an argument of a registered prompt.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the prompt is not registered or does not declare the argument.
+    pub fn complete_argument(
+        &mut self,
+        prompt: &str,
+        argument: &str,
+        completion: Completion,
+    ) -> &mut Self {
+        let declared = self
+            .get(prompt)
+            .unwrap_or_else(|| panic!("prompt '{prompt}' is not registered"))
+            .arguments
+            .iter()
+            .flatten()
+            .any(|arg| arg.name == argument);
+        if !declared {
+            panic!("prompt '{prompt}' has no argument '{argument}'");
+        }
+        self.completions
+            .insert((prompt.to_string(), argument.to_string()), completion);
+        self
+    }
+
+    /// Returns the completion source for an argument of a prompt, failing if the prompt is
+    /// unknown.
+    pub fn completion(&self, prompt: &str, argument: &str) -> Result<Option<&Completion>> {
+        if !self.index.contains_key(prompt) {
+            return Err(McpError::invalid_params(format!("unknown prompt '{prompt}'")).into());
+        }
+        Ok(self
+            .completions
+            .get(&(prompt.to_string(), argument.to_string())))
+    }
+
+    /// Returns the registered prompt with the given name, if any.
+    pub fn get(&self, name: &str) -> Option<&Prompt> {
+        self.index.get(name).map(|&i| &self.prompts[i].0)
+    }
+
+    /// Sets the maximum number of prompts returned per `list_prompts` page.
+    pub fn set_page_size(&mut self, page_size: usize) -> &mut Self {
+        self.paginator = self.paginator.clone().with_page_size(page_size);
+        self
+    }
+
+    /// Lists the registered prompts in registration order, one page at a time.
+    pub fn list(&self, input: ListPromptsRequest) -> Result<ListPromptsResult> {
+        let prompts: Vec<_> = self.prompts.iter().map(|(p, _)| p).collect();
+        let page = self
+            .paginator
+            .paginate("prompts", &prompts, input.cursor.as_deref())?;
+        Ok(ListPromptsResult {
+            prompts: page.items.into_iter().cloned().collect(),
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    /// Renders the prompt registered under `input.request.name`.
+    ///
+    /// Fails without calling the handler if the prompt is unknown or a required argument is
+    /// missing or empty.
+    pub fn render(&self, input: GetPromptRequest) -> Result<GetPromptResult> {
+        let Some(&i) = self.index.get(&input.request.name) else {
+            return Err(McpError::invalid_params(format!(
+                "unknown prompt '{}'; available prompts: [{}]",
+                input.request.name,
+                self.prompts
+                    .iter()
+                    .map(|(p, _)| p.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+            .into());
+        };
+        let (prompt, handler) = &self.prompts[i];
+
+        let missing: Vec<&str> = prompt
+            .arguments
+            .iter()
+            .flatten()
+            .filter(|arg| arg.required == Some(true))
+            .filter(|arg| {
+                input
+                    .request
+                    .arguments
+                    .as_ref()
+                    .and_then(|args| args.get(&arg.name))
+                    .is_none_or(|value| value.is_empty())
+            })
+            .map(|arg| arg.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            return Err(McpError::invalid_params(format!(
+                "missing required arguments for prompt '{}': {}",
+                prompt.name,
+                missing.join(", ")
+            ))
+            .into());
+        }
+
+        handler(input)
+    }
+}
+
+/// Returns the plugin's prompt registry, building it on first use from the Markdown files
+/// embedded from `prompts/` followed by `crate::register_prompts`.
+pub(crate) fn registry() -> &'static PromptRegistry {
+    static REGISTRY: OnceLock<PromptRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut registry = PromptRegistry::default();
+        for (file_name, source) in markdown::EMBEDDED_PROMPTS {
+            registry.register_markdown(file_name, source);
+        }
+        crate::register_prompts(&mut registry);
+        registry
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argument(name: &str, required: Option<bool>) -> PromptArgument {
+        PromptArgument {
+            name: name.to_string(),
+            required,
+            ..Default::default()
+        }
+    }
+
+    fn registry() -> PromptRegistry {
+        let mut registry = PromptRegistry::default();
+        registry.register_template(
+            Prompt {
+                name: "review".to_string(),
+                description: Some("Reviews code".to_string()),
+                arguments: Some(vec![
+                    argument("code", Some(true)),
+                    argument("language", Some(true)),
+                    argument("focus", Some(false)),
+                    argument("tone", None),
+                ]),
+                ..Default::default()
+            },
+            [(
+                Role::User,
+                "Review this {{ language }}{% if focus %} for {{ focus }}{% endif %}:\n{{ code }}",
+            )],
+        );
+        registry
+    }
+
+    fn render(registry: &PromptRegistry, name: &str, arguments: &[(&str, &str)]) -> Result<String> {
+        let result = registry.render(GetPromptRequest {
+            context: PluginRequestContext::default(),
+            request: GetPromptRequestParam {
+                name: name.to_string(),
+                arguments: Some(
+                    arguments
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect(),
+                ),
+            },
+        })?;
+        assert_eq!(result.description.as_deref(), Some("Reviews code"));
+        match result.messages.as_slice() {
+            [
+                PromptMessage {
+                    role: Role::User,
+                    content: ContentBlock::Text(content),
+                },
+            ] => Ok(content.text.clone()),
+            messages => panic!("unexpected messages: {messages:?}"),
+        }
+    }
+
+    fn invalid_params(result: Result<String>) -> String {
+        let error = result.unwrap_err();
+        let error = error.downcast_ref::<McpError>().unwrap();
+        assert_eq!(error.code, McpError::INVALID_PARAMS);
+        error.message.clone()
+    }
+
+    #[test]
+    fn template_prompts_render_their_arguments() {
+        let registry = registry();
+        assert_eq!(
+            render(
+                &registry,
+                "review",
+                &[("code", "x = 1"), ("language", "Python")]
+            )
+            .unwrap(),
+            "Review this Python:\nx = 1"
+        );
+        assert_eq!(
+            render(
+                &registry,
+                "review",
+                &[
+                    ("code", "x = 1"),
+                    ("language", "Python"),
+                    ("focus", "style")
+                ]
+            )
+            .unwrap(),
+            "Review this Python for style:\nx = 1"
+        );
+    }
+
+    #[test]
+    fn missing_or_empty_required_arguments_are_rejected() {
+        let registry = registry();
+        assert_eq!(
+            invalid_params(render(&registry, "review", &[("code", "x = 1")])),
+            "missing required arguments for prompt 'review': language"
+        );
+        assert_eq!(
+            invalid_params(render(
+                &registry,
+                "review",
+                &[("code", ""), ("language", "Python"), ("focus", "")]
+            )),
+            "missing required arguments for prompt 'review': code"
+        );
+        assert_eq!(
+            invalid_params(render(&registry, "review", &[])),
+            "missing required arguments for prompt 'review': code, language"
+        );
+
+        let error = registry
+            .render(GetPromptRequest {
+                context: PluginRequestContext::default(),
+                request: GetPromptRequestParam {
+                    name: "review".to_string(),
+                    arguments: None,
+                },
+            })
+            .unwrap_err();
+        assert!(error.to_string().ends_with("code, language"), "{error}");
+    }
+
+    #[test]
+    fn unknown_prompts_are_rejected() {
+        assert_eq!(
+            invalid_params(render(&registry(), "summarize", &[])),
+            "unknown prompt 'summarize'; available prompts: [review]"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "template for prompt 'greet' uses undeclared argument 'name'")]
+    fn templates_must_only_use_declared_arguments() {
+        PromptRegistry::default().register_template(
+            Prompt {
+                name: "greet".to_string(),
+                ..Default::default()
+            },
+            [(Role::User, "Hello, {{ name }}!")],
+        );
+    }
+}
@@ -0,0 +1,338 @@
+#![allow(unused)]
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use extism_pdk::HttpResponse;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// `*-Reset` values at or above this are Unix timestamps rather than delays in seconds.
+const TIMESTAMP_THRESHOLD: u64 = 1_000_000_000;
+
+/// Rate-limit information from a response's headers.
+///
+/// Understands `Retry-After` (delay in seconds, or an HTTP date in IMF-fixdate/RFC 2822,
+/// RFC 850 or asctime format) and the `RateLimit-Remaining`/`RateLimit-Reset` headers of
+/// the IETF draft together with their `X-RateLimit-*` predecessors used by GitHub-style
+/// APIs. A reset is read as a delay in seconds, or as a Unix timestamp if it is large
+/// enough to be one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Requests left in the current window
+    pub remaining: Option<u64>,
+
+    /// Time until the current window resets
+    pub reset: Option<Duration>,
+
+    /// Time the server asked the client to wait before its next request
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimit {
+    /// Reads the rate-limit headers of `res`.
+    pub fn from_response(res: &HttpResponse) -> Self {
+        Self::from_headers(|name| {
+            res.headers()
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.clone())
+        })
+    }
+
+    /// Reads rate-limit headers through `header`, which looks a header up by name.
+    pub fn from_headers(header: impl Fn(&str) -> Option<String>) -> Self {
+        Self::from_headers_at(header, Utc::now())
+    }
+
+    fn from_headers_at(header: impl Fn(&str) -> Option<String>, now: DateTime<Utc>) -> Self {
+        let first = |names: [&str; 2]| names.into_iter().find_map(&header);
+        RateLimit {
+            remaining: first(["ratelimit-remaining", "x-ratelimit-remaining"])
+                .and_then(|value| value.trim().parse().ok()),
+            reset: first(["ratelimit-reset", "x-ratelimit-reset"])
+                .and_then(|value| parse_reset(&value, now)),
+            retry_after: header("retry-after").and_then(|value| parse_retry_after_at(&value, now)),
+        }
+    }
+
+    /// Returns how long to wait before the next request, if the server asked for a pause
+    /// or the current window is used up.
+    pub fn wait(&self) -> Option<Duration> {
+        self.retry_after
+            .or(self.reset.filter(|_| self.remaining == Some(0)))
+    }
+}
+
+/// Parses a `Retry-After` value into the delay it asks for.
+///
+/// Dates in the past yield a zero delay; unparseable values yield `None`.
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    parse_retry_after_at(value, Utc::now())
+}
+
+fn parse_retry_after_at(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let target = DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+        .or_else(|| {
+            // RFC 850, e.g. "Sunday, 06-Nov-94 08:49:37 GMT". The weekday is skipped rather
+            // than checked, since some servers abbreviate it.
+            let (_, date) = value.split_once(',')?;
+            NaiveDateTime::parse_from_str(date.trim(), "%d-%b-%y %H:%M:%S GMT")
+                .ok()
+                .map(|d| d.and_utc())
+        })
+        .or_else(|| {
+            // asctime, e.g. "Sun Nov  6 08:49:37 1994"
+            NaiveDateTime::parse_from_str(value, "%a %b %e %H:%M:%S %Y")
+                .ok()
+                .map(|d| d.and_utc())
+        })?;
+    Some((target - now).to_std().unwrap_or_default())
+}
+
+fn parse_reset(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value: u64 = value.trim().parse().ok()?;
+    if value < TIMESTAMP_THRESHOLD {
+        return Some(Duration::from_secs(value));
+    }
+    let target = DateTime::from_timestamp(i64::try_from(value).ok()?, 0)?;
+    Some((target - now).to_std().unwrap_or_default())
+}
+
+/// When each host, by scheme and authority, will accept requests again.
+static BLOCKED: Mutex<Option<HashMap<String, Instant>>> = Mutex::new(None);
+
+/// Returns the scheme and authority of `url`, e.g. `https://api.github.com`.
+fn origin(url: &str) -> &str {
+    let start = url.find("://").map_or(0, |i| i + 3);
+    let end = url[start..]
+        .find(['/', '?', '#'])
+        .map_or(url.len(), |i| start + i);
+    &url[..end]
+}
+
+/// Remembers the rate limit that a response from `url` announced.
+///
+/// Later requests to the same host go through [`wait_for`] until the limit resets.
+pub fn record(url: &str, res: &HttpResponse) {
+    let Some(wait) = RateLimit::from_response(res).wait() else {
+        return;
+    };
+    let until = Instant::now() + wait;
+    let mut blocked = BLOCKED.lock().unwrap_or_else(|e| e.into_inner());
+    let blocked = blocked.get_or_insert_with(HashMap::new);
+    let entry = blocked.entry(origin(url).to_string()).or_insert(until);
+    *entry = (*entry).max(until);
+}
+
+/// Waits until the host of `url` accepts requests again, according to earlier responses.
+///
+/// Fails without waiting if that would take longer than `budget`.
+pub fn wait_for(url: &str, budget: Option<Duration>) -> Result<()> {
+    let origin = origin(url);
+    let wait = {
+        let mut blocked = BLOCKED.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(blocked) = blocked.as_mut() else {
+            return Ok(());
+        };
+        let Some(until) = blocked.get(origin).copied() else {
+            return Ok(());
+        };
+        let wait = until.saturating_duration_since(Instant::now());
+        if wait.is_zero() {
+            blocked.remove(origin);
+            return Ok(());
+        }
+        wait
+    };
+
+    if let Some(budget) = budget
+        && wait > budget
+    {
+        return Err(anyhow!(
+            "rate limited by {origin}: the next request is allowed in {}s, which exceeds the remaining retry budget of {}s",
+            wait.as_secs_f64().ceil(),
+            budget.as_secs_f64().floor()
+        ));
+    }
+    thread::sleep(wait);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sun, 06 Nov 1994 08:49:00 GMT, 37 seconds before the dates in RFC 9110's examples.
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc2822("Sun, 06 Nov 1994 08:49:00 GMT")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn retry_after(value: &str) -> Option<Duration> {
+        parse_retry_after_at(value, now())
+    }
+
+    /// A time recent enough for `*-Reset` timestamps to be told apart from delays.
+    fn recent() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_750_000_000, 0).unwrap()
+    }
+
+    fn rate_limit(headers: &[(&str, &str)]) -> RateLimit {
+        RateLimit::from_headers_at(
+            |name| {
+                headers
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                    .map(|(_, value)| value.to_string())
+            },
+            recent(),
+        )
+    }
+
+    #[test]
+    fn retry_after_in_seconds() {
+        assert_eq!(retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(retry_after(" 0 "), Some(Duration::ZERO));
+        assert_eq!(retry_after("-1"), None);
+        assert_eq!(retry_after("1.5"), None);
+    }
+
+    #[test]
+    fn retry_after_as_http_date() {
+        let expected = Some(Duration::from_secs(37));
+        assert_eq!(retry_after("Sun, 06 Nov 1994 08:49:37 GMT"), expected);
+        assert_eq!(retry_after("Sunday, 06-Nov-94 08:49:37 GMT"), expected);
+        assert_eq!(retry_after("Sun Nov  6 08:49:37 1994"), expected);
+    }
+
+    #[test]
+    fn rfc_850_dates_accept_any_weekday_token() {
+        let expected = Some(Duration::from_secs(37));
+        assert_eq!(retry_after("Sun, 06-Nov-94 08:49:37 GMT"), expected);
+        assert_eq!(retry_after("sunday, 06-Nov-94 08:49:37 GMT"), expected);
+        assert_eq!(retry_after("Monday, 06-Nov-94 08:49:37 GMT"), expected);
+    }
+
+    #[test]
+    fn retry_after_in_the_past_or_malformed() {
+        assert_eq!(
+            retry_after("Sat, 05 Nov 1994 08:49:37 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after("soon"), None);
+        assert_eq!(retry_after(""), None);
+        assert_eq!(retry_after("06-Nov-94 08:49:37 GMT"), None);
+    }
+
+    #[test]
+    fn reads_draft_and_x_ratelimit_headers() {
+        assert_eq!(
+            rate_limit(&[("RateLimit-Remaining", "0"), ("RateLimit-Reset", "30")]),
+            RateLimit {
+                remaining: Some(0),
+                reset: Some(Duration::from_secs(30)),
+                retry_after: None,
+            }
+        );
+        let reset = recent().timestamp() + 90;
+        assert_eq!(
+            rate_limit(&[
+                ("X-RateLimit-Remaining", "4"),
+                ("X-RateLimit-Reset", &reset.to_string()),
+            ]),
+            RateLimit {
+                remaining: Some(4),
+                reset: Some(Duration::from_secs(90)),
+                retry_after: None,
+            }
+        );
+        // The draft headers win over their predecessors.
+        assert_eq!(
+            rate_limit(&[("ratelimit-remaining", "1"), ("x-ratelimit-remaining", "9")]).remaining,
+            Some(1)
+        );
+        assert_eq!(
+            rate_limit(&[("X-RateLimit-Remaining", "lots")]),
+            RateLimit::default()
+        );
+    }
+
+    #[test]
+    fn reset_timestamps_in_the_past_are_zero() {
+        let reset = recent().timestamp() - 10;
+        assert_eq!(
+            rate_limit(&[("x-ratelimit-reset", &reset.to_string())]).reset,
+            Some(Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn waits_for_retry_after_or_an_exhausted_window() {
+        let wait = |headers: &[(&str, &str)]| rate_limit(headers).wait();
+        assert_eq!(wait(&[]), None);
+        assert_eq!(
+            wait(&[("Retry-After", "5"), ("RateLimit-Reset", "60")]),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            wait(&[("RateLimit-Remaining", "0"), ("RateLimit-Reset", "60")]),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            wait(&[("RateLimit-Remaining", "3"), ("RateLimit-Reset", "60")]),
+            None
+        );
+    }
+
+    #[test]
+    fn origin_is_scheme_and_authority() {
+        assert_eq!(
+            origin("https://api.github.com/repos/x?page=2"),
+            "https://api.github.com"
+        );
+        assert_eq!(origin("http://localhost:8080"), "http://localhost:8080");
+        assert_eq!(origin("https://example.com#top"), "https://example.com");
+    }
+
+    #[test]
+    fn wait_for_respects_the_budget() {
+        let block = |url: &str, wait: Duration| {
+            BLOCKED
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get_or_insert_with(HashMap::new)
+                .insert(origin(url).to_string(), Instant::now() + wait);
+        };
+
+        block("https://slow.example/a", Duration::from_secs(60));
+        let error = wait_for("https://slow.example/b", Some(Duration::from_secs(5))).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("rate limited by https://slow.example")
+        );
+
+        block("https://done.example/a", Duration::ZERO);
+        wait_for("https://done.example/b", Some(Duration::ZERO)).unwrap();
+        assert!(
+            !BLOCKED
+                .lock()
+                .unwrap()
+                .as_ref()
+                .unwrap()
+                .contains_key("https://done.example")
+        );
+
+        wait_for("https://other.example/", Some(Duration::ZERO)).unwrap();
+    }
+}
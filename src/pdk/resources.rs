@@ -0,0 +1,315 @@
+#![allow(unused)]
+use super::{
+    completion::Completion,
+    error::McpError,
+    pagination::Paginator,
+    types::*,
+    uri_template::{UriTemplate, Variables},
+};
+use anyhow::{Result, anyhow};
+use std::{collections::HashMap, sync::OnceLock};
+
+type ResourceHandler = Box<dyn Fn(ReadResourceRequest) -> Result<ReadResourceResult> + Send + Sync>;
+
+type TemplateHandler =
+    Box<dyn Fn(ReadResourceRequest, Variables) -> Result<ReadResourceResult> + Send + Sync>;
+
+/// A set of resources and resource templates, and the handlers that read them.
+///
+/// Plugins register everything once in `register_resources` in `lib.rs`; the
+/// `list_resources`, `list_resource_templates` and `read_resource` exports are then all
+/// answered from the registry. Reads are routed to a static resource registered under the
+/// exact URI first, then to the handler of the best matching template, which also receives
+/// the variables extracted from the URI. Anything else fails with
+/// [`McpError::resource_not_found`].
+/// Template variables with an attached [`Completion`] are completed by the `complete`
+/// export.
+#[derive(Default)]
+pub struct ResourceRegistry {
+    resources: Vec<(Resource, ResourceHandler)>,
+    index: HashMap<String, usize>,
+    templates: Vec<(ResourceTemplate, UriTemplate, TemplateHandler)>,
+    completions: HashMap<(String, String), Completion>,
+    paginator: Paginator,
+}
+
+impl ResourceRegistry {
+    /// Registers a resource under its exact URI together with the handler that reads it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a resource with the same URI has already been registered.
+    pub fn register_resource<F>(&mut self, resource: Resource, handler: F) -> &mut Self
+    where
+        F: Fn(ReadResourceRequest) -> Result<ReadResourceResult> + Send + Sync + 'static,
+    {
+        if self.index.contains_key(&resource.uri) {
+            panic!("resource '{}' is already registered", resource.uri);
+        }
+        self.index
+            .insert(resource.uri.clone(), self.resources.len());
+        self.resources.push((resource, Box::new(handler)));
+        self
+    }
+
+    /// Registers a resource template together with the handler that reads matching resources.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `template.uri_template` is not a valid RFC 6570 template, or if a template
+    /// with the same pattern has already been registered.
+    pub fn register_template<F>(&mut self, template: ResourceTemplate, handler: F) -> &mut Self
+    where
+        F: Fn(ReadResourceRequest, Variables) -> Result<ReadResourceResult> + Send + Sync + 'static,
+    {
+        let pattern = UriTemplate::parse(&template.uri_template).unwrap_or_else(|e| panic!("{e}"));
+        if self
+            .templates
+            .iter()
+            .any(|(t, _, _)| t.uri_template == template.uri_template)
+        {
+            panic!(
+                "resource template '{}' is already registered",
+                template.uri_template
+            );
+        }
+        self.templates.push((template, pattern, Box::new(handler)));
+        self
+    }
+
+    /// Attaches a completion source to a variable of a registered resource template.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no template with this pattern is registered or it has no such variable.
+    pub fn complete_variable(
+        &mut self,
+        uri_template: &str,
+        variable: &str,
+        completion: Completion,
+    ) -> &mut Self {
+        let (_, pattern, _) = self
+            .templates
+            .iter()
+            .find(|(t, _, _)| t.uri_template == uri_template)
+            .unwrap_or_else(|| panic!("resource template '{uri_template}' is not registered"));
+        if !pattern.variable_names().any(|name| name == variable) {
+            panic!("resource template '{uri_template}' has no variable '{variable}'");
+        }
+        self.completions
+            .insert((uri_template.to_string(), variable.to_string()), completion);
+        self
+    }
+
+    /// Returns the completion source for a variable of a resource template, failing if the
+    /// template is unknown.
+    pub fn completion(&self, uri_template: &str, variable: &str) -> Result<Option<&Completion>> {
+        if !self
+            .templates
+            .iter()
+            .any(|(t, _, _)| t.uri_template == uri_template)
+        {
+            return Err(McpError::invalid_params(format!(
+                "unknown resource template '{uri_template}'"
+            ))
+            .into());
+        }
+        Ok(self
+            .completions
+            .get(&(uri_template.to_string(), variable.to_string())))
+    }
+
+    /// Sets the maximum number of entries returned per list page.
+    pub fn set_page_size(&mut self, page_size: usize) -> &mut Self {
+        self.paginator = self.paginator.clone().with_page_size(page_size);
+        self
+    }
+
+    /// Lists the registered resources in registration order, one page at a time.
+    pub fn list(&self, input: ListResourcesRequest) -> Result<ListResourcesResult> {
+        let resources: Vec<_> = self.resources.iter().map(|(r, _)| r).collect();
+        let page = self
+            .paginator
+            .paginate("resources", &resources, input.cursor.as_deref())?;
+        Ok(ListResourcesResult {
+            resources: page.items.into_iter().cloned().collect(),
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    /// Lists the registered resource templates in registration order, one page at a time.
+    pub fn list_templates(
+        &self,
+        input: ListResourceTemplatesRequest,
+    ) -> Result<ListResourceTemplatesResult> {
+        let templates: Vec<_> = self.templates.iter().map(|(t, _, _)| t).collect();
+        let page =
+            self.paginator
+                .paginate("resource templates", &templates, input.cursor.as_deref())?;
+        Ok(ListResourceTemplatesResult {
+            resource_templates: page.items.into_iter().cloned().collect(),
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    /// Finds the template matching `uri`, returning its index and the extracted variables.
+    ///
+    /// When several templates match, the one with the most literal characters (the most
+    /// specific) wins, and ties go to the earliest registered.
+    fn match_template(&self, uri: &str) -> Option<(usize, Variables)> {
+        self.templates
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (_, pattern, _))| {
+                let vars = pattern.match_uri(uri)?;
+                Some((std::cmp::Reverse(pattern.literal_len()), i, vars))
+            })
+            .min_by_key(|(specificity, i, _)| (*specificity, *i))
+            .map(|(_, i, vars)| (i, vars))
+    }
+
+    /// Reads the resource at `input.request.uri`.
+    ///
+    /// Fails with [`McpError::resource_not_found`] if neither a static resource nor a template matches.
+    pub fn read(&self, input: ReadResourceRequest) -> Result<ReadResourceResult> {
+        if let Some(&i) = self.index.get(&input.request.uri) {
+            return (self.resources[i].1)(input);
+        }
+        match self.match_template(&input.request.uri) {
+            Some((i, vars)) => (self.templates[i].2)(input, vars),
+            None => Err(McpError::resource_not_found(&input.request.uri).into()),
+        }
+    }
+}
+
+/// Returns the plugin's resource registry, building it from `crate::register_resources` on first use.
+pub(crate) fn registry() -> &'static ResourceRegistry {
+    static REGISTRY: OnceLock<ResourceRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut registry = ResourceRegistry::default();
+        crate::register_resources(&mut registry);
+        registry
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resource(uri: &str) -> Resource {
+        Resource {
+            name: uri.to_string(),
+            uri: uri.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn template(uri_template: &str) -> ResourceTemplate {
+        ResourceTemplate {
+            name: uri_template.to_string(),
+            uri_template: uri_template.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn text(text: String) -> Result<ReadResourceResult> {
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::Text(TextResourceContents {
+                text,
+                ..Default::default()
+            })],
+        })
+    }
+
+    /// Registers a template whose handler answers with its pattern and the `name` variable.
+    fn register_template(registry: &mut ResourceRegistry, uri_template: &'static str) {
+        registry.register_template(template(uri_template), move |_, vars| {
+            text(format!(
+                "{uri_template} {}",
+                vars.get_str("name").unwrap_or_default()
+            ))
+        });
+    }
+
+    fn read(registry: &ResourceRegistry, uri: &str) -> Result<String> {
+        let result = registry.read(ReadResourceRequest {
+            context: PluginRequestContext::default(),
+            request: ReadResourceRequestParam {
+                uri: uri.to_string(),
+            },
+        })?;
+        match result.contents.as_slice() {
+            [ResourceContents::Text(content)] => Ok(content.text.clone()),
+            contents => panic!("unexpected contents: {contents:?}"),
+        }
+    }
+
+    fn registry() -> ResourceRegistry {
+        let mut registry = ResourceRegistry::default();
+        register_template(&mut registry, "docs://{+name}");
+        registry.register_resource(resource("docs://readme"), |_| text("static".to_string()));
+        register_template(&mut registry, "docs://guides/{name}");
+        registry
+    }
+
+    #[test]
+    fn static_resources_take_precedence_over_templates() {
+        assert_eq!(read(&registry(), "docs://readme").unwrap(), "static");
+        assert_eq!(
+            read(&registry(), "docs://license").unwrap(),
+            "docs://{+name} license"
+        );
+    }
+
+    #[test]
+    fn the_template_with_the_most_literal_characters_wins() {
+        assert_eq!(
+            read(&registry(), "docs://tutorials/setup").unwrap(),
+            "docs://{+name} tutorials/setup"
+        );
+        assert_eq!(
+            read(&registry(), "docs://guides/setup").unwrap(),
+            "docs://guides/{name} setup"
+        );
+    }
+
+    #[test]
+    fn ties_go_to_the_first_registered_template() {
+        let mut registry = ResourceRegistry::default();
+        register_template(&mut registry, "db://{name}/rows");
+        register_template(&mut registry, "db://user/{name}");
+        assert_eq!(
+            read(&registry, "db://user/rows").unwrap(),
+            "db://{name}/rows user"
+        );
+
+        let mut registry = ResourceRegistry::default();
+        register_template(&mut registry, "db://user/{name}");
+        register_template(&mut registry, "db://{name}/rows");
+        assert_eq!(
+            read(&registry, "db://user/rows").unwrap(),
+            "db://user/{name} rows"
+        );
+    }
+
+    #[test]
+    fn unmatched_uris_are_not_found() {
+        let error = read(&registry(), "files://readme").unwrap_err();
+        let error = error.downcast_ref::<McpError>().unwrap();
+        assert_eq!(error.code, McpError::RESOURCE_NOT_FOUND);
+        assert_eq!(error.code, -32002);
+        assert_eq!(error, &McpError::resource_not_found("files://readme"));
+    }
+
+    #[test]
+    #[should_panic(expected = "resource 'docs://readme' is already registered")]
+    fn duplicate_resources_panic() {
+        registry().register_resource(resource("docs://readme"), |_| text(String::new()));
+    }
+
+    #[test]
+    #[should_panic(expected = "resource template 'docs://{+name}' is already registered")]
+    fn duplicate_templates_panic() {
+        register_template(&mut registry(), "docs://{+name}");
+    }
+}
@@ -0,0 +1,238 @@
+#![allow(unused)]
+use super::{imports, types::*};
+use anyhow::{Result, anyhow};
+use extism_pdk::config;
+use serde::{
+    Deserialize, Deserializer,
+    de::{DeserializeOwned, IgnoredAny},
+};
+use serde_json::{Value, error::Category};
+use std::fmt;
+
+/// A value that is never printed.
+///
+/// `Debug` and `Display` show `[REDACTED]` instead of the value, so secrets cannot leak
+/// into logs or error messages by accident; use [`expose`](Secret::expose) where the value
+/// is actually needed.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    /// Wraps a value.
+    pub fn new(value: T) -> Self {
+        Secret(value)
+    }
+
+    /// Returns the secret value.
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    /// Unwraps the secret value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret([REDACTED])")
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Secret)
+    }
+}
+
+/// Typed access to the plugin's secrets.
+///
+/// A secret is looked up by name in the host keyring (under the service, which defaults to
+/// the plugin's name), then in the plugin's Extism config under the same name. Values are
+/// decoded from UTF-8 and then as JSON, or as a plain string when they are not JSON.
+///
+/// The user is never asked for a missing secret: the MCP specification forbids collecting
+/// sensitive data through form elicitation, so a secret that is not configured is an error.
+/// Errors never quote a secret's value.
+#[derive(Debug, Clone)]
+pub struct Secrets {
+    service: String,
+}
+
+impl Default for Secrets {
+    fn default() -> Self {
+        Secrets {
+            service: env!("CARGO_PKG_NAME").to_string(),
+        }
+    }
+}
+
+impl Secrets {
+    /// Looks secrets up under the plugin's name as keyring service.
+    pub fn new() -> Self {
+        Secrets::default()
+    }
+
+    /// Sets the keyring service the secrets are stored under.
+    pub fn with_service(mut self, service: impl Into<String>) -> Self {
+        self.service = service.into();
+        self
+    }
+
+    /// Returns the secret `name` as a string.
+    pub fn get_string(&self, name: &str) -> Result<Secret<String>> {
+        self.lookup(name).map(Secret)
+    }
+
+    /// Returns the secret `name` decoded as `T`.
+    pub fn get<T: DeserializeOwned>(&self, name: &str) -> Result<Secret<T>> {
+        decode(name, self.lookup(name)?).map(Secret)
+    }
+
+    /// Returns the secret `name`, or `None` if it is not found anywhere.
+    pub fn find(&self, name: &str) -> Result<Option<Secret<String>>> {
+        if let Some(value) = self.keyring_value(name) {
+            return Ok(Some(Secret(value)));
+        }
+        if let Some(value) = config::get(name)?.filter(|value| !value.is_empty()) {
+            return Ok(Some(Secret(value)));
+        }
+        Ok(None)
+    }
+
+    fn lookup(&self, name: &str) -> Result<String> {
+        self.find(name)?.map(Secret::into_inner).ok_or_else(|| {
+            anyhow!(
+                "secret '{name}' is not configured: store it in the keyring under service '{}' or set it in the plugin config",
+                self.service
+            )
+        })
+    }
+
+    fn keyring_value(&self, name: &str) -> Option<String> {
+        let bytes = imports::get_keyring_secret(KeyringEntryId {
+            service: self.service.clone(),
+            user: name.to_string(),
+        })
+        .ok()?;
+        String::from_utf8(bytes)
+            .ok()
+            .filter(|value| !value.is_empty())
+    }
+}
+
+/// Decodes `raw` as JSON, or as a plain string when it does not decode as JSON.
+///
+/// The error names only the kind of problem and where it is, since serde's messages quote
+/// the offending value. When `raw` is valid JSON, the error is the one decoding it as JSON,
+/// which points into the value.
+fn decode<T: DeserializeOwned>(name: &str, raw: String) -> Result<T> {
+    let json_error = match serde_json::from_str(&raw) {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+    let is_json = serde_json::from_str::<IgnoredAny>(&raw).is_ok();
+    let e = match serde_json::from_value(Value::String(raw)) {
+        Ok(value) => return Ok(value),
+        Err(_) if is_json => json_error,
+        Err(e) => e,
+    };
+    let kind = match e.classify() {
+        Category::Io => "read error",
+        Category::Syntax => "syntax error",
+        Category::Data => "wrong type or value",
+        Category::Eof => "unexpected end of input",
+    };
+    let at = if e.line() > 0 {
+        format!(" at line {} column {}", e.line(), e.column())
+    } else {
+        String::new()
+    };
+    Err(anyhow!(
+        "secret '{name}' cannot be decoded as {}: {kind}{at}",
+        std::any::type_name::<T>()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    const SECRET: &str = "hunter2-super-secret";
+
+    #[test]
+    fn decodes_json_and_plain_strings() {
+        assert_eq!(decode::<u32>("PORT", "8080".to_string()).unwrap(), 8080);
+        assert_eq!(decode::<String>("KEY", SECRET.to_string()).unwrap(), SECRET);
+        assert_eq!(
+            decode::<String>("KEY", "\"quoted\"".to_string()).unwrap(),
+            "quoted"
+        );
+        assert_eq!(decode::<String>("PIN", "1234".to_string()).unwrap(), "1234");
+    }
+
+    #[test]
+    fn decode_errors_do_not_quote_the_secret() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Credentials {
+            user: String,
+            port: u16,
+        }
+
+        let errors = [
+            decode::<u32>("X", SECRET.to_string()).unwrap_err(),
+            decode::<Credentials>("X", SECRET.to_string()).unwrap_err(),
+            decode::<Credentials>("X", format!(r#"{{"user": "a", "port": "{SECRET}"}}"#))
+                .unwrap_err(),
+            decode::<Vec<u8>>("X", format!(r#"["{SECRET}"]"#)).unwrap_err(),
+        ];
+        for e in errors {
+            let message = format!("{e:#}");
+            assert!(
+                message.starts_with("secret 'X' cannot be decoded"),
+                "{message}"
+            );
+            assert!(!message.contains("hunter2"), "secret leaked: {message}");
+        }
+    }
+
+    #[test]
+    fn decode_errors_say_where_json_values_are_wrong() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Credentials {
+            user: String,
+            port: u16,
+        }
+
+        let message = decode::<Credentials>("X", "{\n  \"user\": \"a\",\n  \"port\": -1\n}".into())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.ends_with(": wrong type or value at line 3 column 12"),
+            "{message}"
+        );
+
+        let message = decode::<u32>("X", SECRET.to_string())
+            .unwrap_err()
+            .to_string();
+        assert!(message.ends_with(": wrong type or value"), "{message}");
+    }
+
+    #[test]
+    fn secret_is_redacted() {
+        let secret = Secret::new(SECRET.to_string());
+        assert_eq!(format!("{secret:?}"), "Secret([REDACTED])");
+        assert_eq!(secret.to_string(), "[REDACTED]");
+        assert_eq!(secret.expose(), SECRET);
+    }
+}
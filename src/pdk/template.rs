@@ -0,0 +1,460 @@
+#![allow(unused)]
+use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+
+/// A parsed prompt template.
+///
+/// The language is deliberately small:
+///
+/// - `{{ name }}` inserts the value of `name`, or nothing if it is not set.
+/// - `{% if name %}…{% elif other %}…{% else %}…{% endif %}` renders the first branch whose
+///   variable is set and non-empty; `{% if not name %}` negates the test.
+/// - `{% for item in name %}…{% endfor %}` renders its body once per item of `name`, split
+///   on newlines if the value has any and on commas otherwise, with items trimmed and empty
+///   items skipped. Inside the loop `{{ item }}`, `{{ loop.index }}` (from 1),
+///   `loop.first` and `loop.last` are available.
+///
+/// A `{% … %}` tag that sits alone on its line removes that whole line from the output, so
+/// block tags can be laid out on their own lines without leaving blank lines behind.
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Var(String),
+    If {
+        branches: Vec<(Condition, Vec<Node>)>,
+        otherwise: Vec<Node>,
+    },
+    For {
+        item: String,
+        source: String,
+        body: Vec<Node>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Condition {
+    name: String,
+    negated: bool,
+}
+
+type Tokens<'a> = std::vec::IntoIter<Token<'a>>;
+
+#[derive(Debug)]
+enum Token<'a> {
+    Text(&'a str),
+    Var(&'a str),
+    Tag(&'a str),
+}
+
+impl Template {
+    /// Parses a template, failing on malformed tags or unbalanced blocks.
+    pub fn parse(source: &str) -> Result<Template> {
+        let mut tokens = tokenize(source)?.into_iter();
+        let (nodes, end) = parse_nodes(&mut tokens)?;
+        match end {
+            None => Ok(Template { nodes }),
+            Some(tag) => Err(anyhow!("unexpected {{% {tag} %}}")),
+        }
+    }
+
+    /// Renders the template with `vars`.
+    pub fn render(&self, vars: &HashMap<String, String>) -> String {
+        let mut scope = Scope {
+            vars,
+            locals: Vec::new(),
+        };
+        let mut out = String::new();
+        render_nodes(&self.nodes, &mut scope, &mut out);
+        out
+    }
+
+    /// Returns the names of the variables the template reads, excluding loop locals.
+    pub fn variables(&self) -> Vec<String> {
+        fn collect(nodes: &[Node], locals: &mut Vec<String>, out: &mut Vec<String>) {
+            let mut add = |name: &str, locals: &Vec<String>, out: &mut Vec<String>| {
+                let is_local = locals.iter().any(|l| l == name) || name.starts_with("loop.");
+                if !is_local && !out.iter().any(|n| n == name) {
+                    out.push(name.to_string());
+                }
+            };
+            for node in nodes {
+                match node {
+                    Node::Text(_) => {}
+                    Node::Var(name) => add(name, locals, out),
+                    Node::If {
+                        branches,
+                        otherwise,
+                    } => {
+                        for (condition, body) in branches {
+                            add(&condition.name, locals, out);
+                            collect(body, locals, out);
+                        }
+                        collect(otherwise, locals, out);
+                    }
+                    Node::For { item, source, body } => {
+                        add(source, locals, out);
+                        locals.push(item.clone());
+                        collect(body, locals, out);
+                        locals.pop();
+                    }
+                }
+            }
+        }
+
+        let mut out = Vec::new();
+        collect(&self.nodes, &mut Vec::new(), &mut out);
+        out
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < source.len() {
+        let rest = &source[pos..];
+        let next = [rest.find("{{"), rest.find("{%")]
+            .into_iter()
+            .flatten()
+            .min();
+        let Some(offset) = next else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+        let start = pos + offset;
+
+        let (close, is_tag) = if source[start..].starts_with("{{") {
+            ("}}", false)
+        } else {
+            ("%}", true)
+        };
+        let end = source[start + 2..]
+            .find(close)
+            .map(|i| start + 2 + i + 2)
+            .ok_or_else(|| anyhow!("unclosed '{}' in template", &source[start..start + 2]))?;
+        let inner = source[start + 2..end - 2].trim();
+
+        let mut text_end = start;
+        let mut next_pos = end;
+        if is_tag {
+            // A tag alone on its line takes the whole line with it.
+            let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = source[end..]
+                .find('\n')
+                .map_or(source.len(), |i| end + i + 1);
+            if source[line_start..start].trim().is_empty()
+                && source[end..line_end].trim().is_empty()
+            {
+                text_end = line_start.max(pos);
+                next_pos = line_end;
+            }
+        }
+        if text_end > pos {
+            tokens.push(Token::Text(&source[pos..text_end]));
+        }
+
+        if is_tag {
+            tokens.push(Token::Tag(inner));
+        } else {
+            if !is_identifier(inner) {
+                return Err(anyhow!("invalid variable '{{{{ {inner} }}}}' in template"));
+            }
+            tokens.push(Token::Var(inner));
+        }
+        pos = next_pos;
+    }
+
+    Ok(tokens)
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Parses nodes until the end of input or a block-closing tag, which is returned.
+fn parse_nodes<'a>(tokens: &mut Tokens<'a>) -> Result<(Vec<Node>, Option<&'a str>)> {
+    let mut nodes = Vec::new();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Text(text) => nodes.push(Node::Text(text.to_string())),
+            Token::Var(name) => nodes.push(Node::Var(name.to_string())),
+            Token::Tag(tag) => {
+                let mut words = tag.split_whitespace();
+                match words.next() {
+                    Some("if") => nodes.push(parse_if(parse_condition(tag, words)?, tokens)?),
+                    Some("for") => {
+                        let (Some(item), Some("in"), Some(source), None) =
+                            (words.next(), words.next(), words.next(), words.next())
+                        else {
+                            return Err(anyhow!(
+                                "expected {{% for <item> in <name> %}}, found {{% {tag} %}}"
+                            ));
+                        };
+                        let (body, end) = parse_nodes(tokens)?;
+                        if end != Some("endfor") {
+                            return Err(anyhow!("{{% {tag} %}} is missing {{% endfor %}}"));
+                        }
+                        nodes.push(Node::For {
+                            item: item.to_string(),
+                            source: source.to_string(),
+                            body,
+                        });
+                    }
+                    Some("elif" | "else" | "endif" | "endfor") => return Ok((nodes, Some(tag))),
+                    _ => return Err(anyhow!("unknown tag {{% {tag} %}}")),
+                }
+            }
+        }
+    }
+
+    Ok((nodes, None))
+}
+
+fn parse_condition<'a>(tag: &str, mut words: impl Iterator<Item = &'a str>) -> Result<Condition> {
+    let condition = match (words.next(), words.next(), words.next()) {
+        (Some("not"), Some(name), None) => Condition {
+            name: name.to_string(),
+            negated: true,
+        },
+        (Some(name), None, None) => Condition {
+            name: name.to_string(),
+            negated: false,
+        },
+        _ => return Err(anyhow!("invalid condition in {{% {tag} %}}")),
+    };
+    if !is_identifier(&condition.name) {
+        return Err(anyhow!("invalid condition in {{% {tag} %}}"));
+    }
+    Ok(condition)
+}
+
+fn parse_if<'a>(first: Condition, tokens: &mut Tokens<'a>) -> Result<Node> {
+    let mut branches = Vec::new();
+    let mut condition = first;
+
+    loop {
+        let (body, end) = parse_nodes(tokens)?;
+        branches.push((condition, body));
+        let Some(end) = end else {
+            return Err(anyhow!("{{% if %}} is missing {{% endif %}}"));
+        };
+        let mut words = end.split_whitespace();
+        match words.next() {
+            Some("elif") => condition = parse_condition(end, words)?,
+            Some("else") => {
+                let (otherwise, end) = parse_nodes(tokens)?;
+                if end != Some("endif") {
+                    return Err(anyhow!("{{% else %}} is missing {{% endif %}}"));
+                }
+                return Ok(Node::If {
+                    branches,
+                    otherwise,
+                });
+            }
+            Some("endif") => {
+                return Ok(Node::If {
+                    branches,
+                    otherwise: Vec::new(),
+                });
+            }
+            _ => return Err(anyhow!("unexpected {{% {end} %}} inside {{% if %}}")),
+        }
+    }
+}
+
+struct Scope<'a> {
+    vars: &'a HashMap<String, String>,
+    locals: Vec<(String, String)>,
+}
+
+impl Scope<'_> {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.locals
+            .iter()
+            .rev()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+            .or_else(|| self.vars.get(name).map(String::as_str))
+    }
+}
+
+/// Splits a variable into loop items: by lines if it has any, otherwise by commas.
+fn split_items(value: &str) -> Vec<&str> {
+    let separator = if value.contains('\n') { '\n' } else { ',' };
+    value
+        .split(separator)
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn render_nodes(nodes: &[Node], scope: &mut Scope<'_>, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var(name) => out.push_str(scope.get(name).unwrap_or_default()),
+            Node::If {
+                branches,
+                otherwise,
+            } => {
+                let body = branches
+                    .iter()
+                    .find(|(condition, _)| {
+                        let set = scope.get(&condition.name).is_some_and(|v| !v.is_empty());
+                        set != condition.negated
+                    })
+                    .map_or(otherwise, |(_, body)| body);
+                render_nodes(body, scope, out);
+            }
+            Node::For { item, source, body } => {
+                let value = scope.get(source).unwrap_or_default().to_string();
+                let items = split_items(&value);
+                for (i, value) in items.iter().enumerate() {
+                    let depth = scope.locals.len();
+                    scope.locals.extend([
+                        (item.clone(), value.to_string()),
+                        ("loop.index".to_string(), (i + 1).to_string()),
+                        (
+                            "loop.first".to_string(),
+                            if i == 0 { "true" } else { "" }.to_string(),
+                        ),
+                        (
+                            "loop.last".to_string(),
+                            if i + 1 == items.len() { "true" } else { "" }.to_string(),
+                        ),
+                    ]);
+                    render_nodes(body, scope, out);
+                    scope.locals.truncate(depth);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str, vars: &[(&str, &str)]) -> String {
+        let vars = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Template::parse(source).unwrap().render(&vars)
+    }
+
+    #[test]
+    fn substitutes_variables() {
+        assert_eq!(
+            render("Hello, {{ name }}!", &[("name", "Ada")]),
+            "Hello, Ada!"
+        );
+        assert_eq!(render("Hello, {{name}}!", &[]), "Hello, !");
+        assert_eq!(render("no tags at all", &[]), "no tags at all");
+    }
+
+    #[test]
+    fn renders_the_first_matching_branch() {
+        let source = "{% if a %}A{% elif b %}B{% else %}C{% endif %}";
+        assert_eq!(render(source, &[("a", "1"), ("b", "1")]), "A");
+        assert_eq!(render(source, &[("b", "1")]), "B");
+        assert_eq!(render(source, &[]), "C");
+        assert_eq!(render(source, &[("a", "")]), "C");
+        assert_eq!(render("{% if a %}A{% endif %}", &[]), "");
+    }
+
+    #[test]
+    fn negated_conditions() {
+        let source = "{% if not topic %}general{% else %}{{ topic }}{% endif %}";
+        assert_eq!(render(source, &[]), "general");
+        assert_eq!(render(source, &[("topic", "rust")]), "rust");
+    }
+
+    #[test]
+    fn loops_over_lines_or_commas() {
+        let source = "{% for f in files %}[{{ loop.index }}:{{ f }}]{% endfor %}";
+        assert_eq!(
+            render(source, &[("files", "a.rs, b.rs,,c.rs")]),
+            "[1:a.rs][2:b.rs][3:c.rs]"
+        );
+        assert_eq!(
+            render(source, &[("files", "one, two\nthree\n\n")]),
+            "[1:one, two][2:three]"
+        );
+        assert_eq!(render(source, &[]), "");
+    }
+
+    #[test]
+    fn loop_first_and_last() {
+        let source = "{% for x in xs %}{% if loop.first %}({% endif %}{{ x }}{% if loop.last %}){% else %}, {% endif %}{% endfor %}";
+        assert_eq!(render(source, &[("xs", "a,b,c")]), "(a, b, c)");
+        assert_eq!(render(source, &[("xs", "a")]), "(a)");
+    }
+
+    #[test]
+    fn nested_loops_shadow_and_restore_locals() {
+        let source =
+            "{% for x in xs %}{{ x }}{% for x in ys %}{{ x }}{% endfor %}{{ x }};{% endfor %}";
+        assert_eq!(
+            render(source, &[("xs", "a,b"), ("ys", "1,2")]),
+            "a12a;b12b;"
+        );
+    }
+
+    #[test]
+    fn block_tags_on_their_own_lines_leave_no_blank_lines() {
+        let source = "Review:\n{% for f in files %}\n- {{ f }}\n{% endfor %}\n{% if note %}\nNote: {{ note }}\n{% endif %}\nDone.\n";
+        assert_eq!(
+            render(source, &[("files", "a.rs,b.rs")]),
+            "Review:\n- a.rs\n- b.rs\nDone.\n"
+        );
+        assert_eq!(
+            render(source, &[("files", "a.rs"), ("note", "careful")]),
+            "Review:\n- a.rs\nNote: careful\nDone.\n"
+        );
+        // Tags sharing a line with text keep the line.
+        assert_eq!(render("a {% if x %}b{% endif %}\nc", &[]), "a \nc");
+    }
+
+    #[test]
+    fn lists_variables_without_loop_locals() {
+        let template = Template::parse(
+            "{{ a }}{% if b %}{% for x in c %}{{ x }}{{ loop.index }}{{ d }}{% endfor %}{% endif %}{{ a }}",
+        )
+        .unwrap();
+        assert_eq!(template.variables(), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for (source, error) in [
+            ("{{ name", "unclosed '{{'"),
+            ("{% if a %}", "missing {% endif %}"),
+            ("{% if a %}{% endfor %}", "unexpected {% endfor %}"),
+            ("{% for x in xs %}", "missing {% endfor %}"),
+            (
+                "{% for x of xs %}{% endfor %}",
+                "expected {% for <item> in <name> %}",
+            ),
+            ("{% endif %}", "unexpected {% endif %}"),
+            ("{% if a b %}{% endif %}", "invalid condition"),
+            ("{% include x %}", "unknown tag"),
+            ("{{ a b }}", "invalid variable"),
+            (
+                "{% if a %}{% else %}{% elif b %}{% endif %}",
+                "missing {% endif %}",
+            ),
+        ] {
+            let message = Template::parse(source).unwrap_err().to_string();
+            assert!(message.contains(error), "{source}: {message}");
+        }
+    }
+}
@@ -0,0 +1,403 @@
+#![allow(unused)]
+use super::{
+    cancellation::Cancelled, error::McpError, pagination::Paginator, types::*, validation,
+};
+use anyhow::{Context, Result, anyhow};
+use schemars::{JsonSchema, schema_for};
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use std::{collections::HashMap, sync::OnceLock};
+
+type ToolHandler = Box<dyn Fn(CallToolRequest) -> Result<CallToolResult> + Send + Sync>;
+
+/// A set of tools and the handlers that execute them.
+///
+/// Plugins register each tool once in `register_tools` in `lib.rs`; the
+/// `list_tools` and `call_tool` exports are then driven from the registry so
+/// the advertised tools and the dispatched tools can never drift apart.
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: Vec<(Tool, ToolHandler)>,
+    index: HashMap<String, usize>,
+    paginator: Paginator,
+}
+
+impl ToolRegistry {
+    /// Registers a tool definition together with the handler that executes it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a tool with the same name has already been registered.
+    pub fn register<F>(&mut self, tool: Tool, handler: F) -> &mut Self
+    where
+        F: Fn(CallToolRequest) -> Result<CallToolResult> + Send + Sync + 'static,
+    {
+        if self.index.contains_key(&tool.name) {
+            panic!("tool '{}' is already registered", tool.name);
+        }
+        self.index.insert(tool.name.clone(), self.tools.len());
+        self.tools.push((tool, Box::new(handler)));
+        self
+    }
+
+    /// Registers a tool whose arguments and result are Rust types.
+    ///
+    /// The tool's `input_schema` and `output_schema` are generated from `Args` and `Out`,
+    /// overwriting whatever `tool` carried. On each call the request arguments are
+    /// deserialized into `Args`; like a schema violation, a failure to do so is returned as an
+    /// error result without running the handler. The handler's `Out` is returned both as
+    /// `structured_content` and as a text fallback for clients that ignore structured
+    /// results. `Out` types that do not serialize to a JSON object are returned as text only.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a tool with the same name has already been registered.
+    pub fn register_typed<Args, Out, F>(&mut self, mut tool: Tool, handler: F) -> &mut Self
+    where
+        Args: DeserializeOwned + JsonSchema,
+        Out: Serialize + JsonSchema,
+        F: Fn(&PluginRequestContext, Args) -> Result<Out> + Send + Sync + 'static,
+    {
+        tool.input_schema = schema_for!(Args);
+        let output_schema = schema_for!(Out);
+        tool.output_schema =
+            (output_schema.get("type") == Some(&Value::from("object"))).then_some(output_schema);
+
+        let name = tool.name.clone();
+        self.register(tool, move |input| {
+            let arguments = Value::Object(input.request.arguments.unwrap_or_default());
+            let args = match serde_json::from_value(arguments) {
+                Ok(args) => args,
+                Err(e) => {
+                    return Ok(CallToolResult::error(format!(
+                        "invalid arguments for tool '{name}': {e}"
+                    )));
+                }
+            };
+            let out = serde_json::to_value(handler(&input.context, args)?)
+                .with_context(|| format!("failed to serialize result of tool '{name}'"))?;
+            Ok(typed_result(out))
+        })
+    }
+
+    /// Returns the registered tool with the given name, if any.
+    pub fn get(&self, name: &str) -> Option<&Tool> {
+        self.index.get(name).map(|&i| &self.tools[i].0)
+    }
+
+    /// Returns the names of all registered tools in registration order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.tools.iter().map(|(tool, _)| tool.name.as_str())
+    }
+
+    /// Sets the maximum number of tools returned per `list_tools` page.
+    pub fn set_page_size(&mut self, page_size: usize) -> &mut Self {
+        self.paginator = self.paginator.clone().with_page_size(page_size);
+        self
+    }
+
+    /// Lists the registered tools in registration order, one page at a time.
+    pub fn list(&self, input: ListToolsRequest) -> Result<ListToolsResult> {
+        let tools: Vec<_> = self.tools.iter().map(|(tool, _)| tool).collect();
+        let page = self
+            .paginator
+            .paginate("tools", &tools, input.cursor.as_deref())?;
+        Ok(ListToolsResult {
+            tools: page.items.into_iter().cloned().collect(),
+            next_cursor: page.next_cursor,
+        })
+    }
+
+    /// Dispatches a tool call to the handler registered under `input.request.name`.
+    ///
+    /// The call's arguments are first validated against the tool's `input_schema`; if they
+    /// do not conform, the handler is not run and an error result listing every violation
+    /// is returned instead, so the caller can correct the arguments and retry.
+    ///
+    /// An `Err` from the handler is a tool execution failure: it is returned as an error
+    /// result carrying the full error chain, so the model can see what went wrong and
+    /// self-correct. Only errors that carry an [`McpError`], such as an unknown tool, or a
+    /// [`Cancelled`] request are returned as protocol errors.
+    ///
+    /// With the `validate-output` feature (and always in tests), successful results are also
+    /// checked against the tool's `output_schema`, and a mismatch is turned into an error
+    /// result naming the offending JSON pointer paths.
+    pub fn call(&self, input: CallToolRequest) -> Result<CallToolResult> {
+        let Some(&i) = self.index.get(&input.request.name) else {
+            return Err(McpError::invalid_params(format!(
+                "unknown tool '{}'; available tools: [{}]",
+                input.request.name,
+                self.names().collect::<Vec<_>>().join(", ")
+            ))
+            .into());
+        };
+        let (tool, handler) = &self.tools[i];
+
+        let arguments = Value::Object(input.request.arguments.clone().unwrap_or_default());
+        let violations = validation::validate(&tool.input_schema, &arguments);
+        if !violations.is_empty() {
+            return Ok(CallToolResult::error(format!(
+                "invalid arguments for tool '{}':\n{}",
+                tool.name,
+                list_violations(&violations)
+            )));
+        }
+
+        let result = match handler(input) {
+            Ok(result) => result,
+            Err(e)
+                if e.chain()
+                    .any(|cause| cause.is::<McpError>() || cause.is::<Cancelled>()) =>
+            {
+                return Err(e);
+            }
+            Err(e) => return Ok(CallToolResult::error(format!("{e:#}"))),
+        };
+
+        #[cfg(any(test, feature = "validate-output"))]
+        if let Some(output_schema) = &tool.output_schema {
+            let violations = output_violations(output_schema, &result);
+            if !violations.is_empty() {
+                return Ok(CallToolResult::error(format!(
+                    "tool '{}' returned a result that does not match its output schema:\n{}",
+                    tool.name,
+                    list_violations(&violations)
+                )));
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Formats schema violations as a bulleted list, one per line.
+fn list_violations(violations: &[validation::Violation]) -> String {
+    violations
+        .iter()
+        .map(|v| format!("- {v}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Checks a successful result's `structured_content` against the tool's `output_schema`.
+///
+/// Error results are exempt, since the MCP spec only requires structured content on success.
+#[cfg(any(test, feature = "validate-output"))]
+fn output_violations(
+    output_schema: &schemars::Schema,
+    result: &CallToolResult,
+) -> Vec<validation::Violation> {
+    if result.is_error == Some(true) {
+        return Vec::new();
+    }
+    match &result.structured_content {
+        Some(content) => validation::validate(output_schema, &Value::Object(content.clone())),
+        None => vec![validation::Violation {
+            path: String::new(),
+            message: "structured_content is missing but the tool declares an output schema"
+                .to_string(),
+        }],
+    }
+}
+
+/// Builds a `CallToolResult` from a typed tool's serialized output.
+fn typed_result(out: Value) -> CallToolResult {
+    let text = match &out {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    CallToolResult {
+        content: vec![ContentBlock::Text(TextContent {
+            text,
+            ..Default::default()
+        })],
+        structured_content: match out {
+            Value::Object(map) => Some(map),
+            _ => None,
+        },
+        ..Default::default()
+    }
+}
+
+/// Returns the plugin's tool registry, building it from `crate::register_tools` on first use.
+pub(crate) fn registry() -> &'static ToolRegistry {
+    static REGISTRY: OnceLock<ToolRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut registry = ToolRegistry::default();
+        crate::register_tools(&mut registry);
+        registry
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct AddArgs {
+        a: i64,
+        b: i64,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    struct HalfArgs {
+        #[serde(deserialize_with = "even")]
+        n: i64,
+    }
+
+    fn even<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+        let n = i64::deserialize(deserializer)?;
+        if n % 2 != 0 {
+            return Err(serde::de::Error::custom(format!("{n} is odd")));
+        }
+        Ok(n)
+    }
+
+    #[derive(Serialize, JsonSchema)]
+    struct Sum {
+        sum: i64,
+    }
+
+    fn tool(name: &str) -> Tool {
+        Tool {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn call(registry: &ToolRegistry, name: &str, arguments: Value) -> Result<CallToolResult> {
+        registry.call(CallToolRequest {
+            context: PluginRequestContext::default(),
+            request: CallToolRequestParam {
+                name: name.to_string(),
+                arguments: arguments.as_object().cloned(),
+            },
+        })
+    }
+
+    fn text(result: &CallToolResult) -> &str {
+        match result.content.first() {
+            Some(ContentBlock::Text(content)) => &content.text,
+            _ => panic!("result has no text content: {result:?}"),
+        }
+    }
+
+    fn registry() -> ToolRegistry {
+        let mut registry = ToolRegistry::default();
+        registry.register_typed(tool("add"), |_, args: AddArgs| {
+            Ok(Sum {
+                sum: args.a + args.b,
+            })
+        });
+        registry
+    }
+
+    #[test]
+    fn typed_tool_returns_structured_content() {
+        let result = call(&registry(), "add", json!({"a": 2, "b": 3})).unwrap();
+        assert_eq!(result.is_error, None);
+        assert_eq!(
+            result.structured_content,
+            json!({"sum": 5}).as_object().cloned()
+        );
+        assert_eq!(text(&result), r#"{"sum":5}"#);
+    }
+
+    #[test]
+    fn invalid_arguments_are_reported_without_running_the_handler() {
+        let result = call(&registry(), "add", json!({"a": "two"})).unwrap();
+        assert_eq!(result.is_error, Some(true));
+        let message = text(&result);
+        assert!(message.starts_with("invalid arguments for tool 'add':"));
+        assert!(message.contains("- /a:"), "{message}");
+        assert!(message.contains("'b'"), "{message}");
+    }
+
+    #[test]
+    fn arguments_that_fail_to_deserialize_are_an_error_result() {
+        let mut registry = ToolRegistry::default();
+        registry.register_typed(tool("half"), |_, args: HalfArgs| {
+            Ok(Sum { sum: args.n / 2 })
+        });
+        let result = call(&registry, "half", json!({"n": 4})).unwrap();
+        assert_eq!(
+            result.structured_content,
+            json!({"sum": 2}).as_object().cloned()
+        );
+
+        let result = call(&registry, "half", json!({"n": 3})).unwrap();
+        assert_eq!(result.is_error, Some(true));
+        assert_eq!(text(&result), "invalid arguments for tool 'half': 3 is odd");
+    }
+
+    #[test]
+    fn unknown_tool_is_a_protocol_error() {
+        let error = call(&registry(), "subtract", json!({})).unwrap_err();
+        let error = error.downcast_ref::<McpError>().unwrap();
+        assert_eq!(error.code, McpError::INVALID_PARAMS);
+        assert!(error.message.contains("available tools: [add]"));
+    }
+
+    #[test]
+    fn handler_errors_become_error_results() {
+        let mut registry = ToolRegistry::default();
+        registry.register(tool("fail"), |_| {
+            Err(anyhow!("backend unavailable").context("failed to fetch"))
+        });
+        let result = call(&registry, "fail", json!({})).unwrap();
+        assert_eq!(result.is_error, Some(true));
+        assert_eq!(text(&result), "failed to fetch: backend unavailable");
+    }
+
+    #[test]
+    fn cancellation_is_not_an_error_result() {
+        let mut registry = ToolRegistry::default();
+        registry.register(tool("stop"), |_| {
+            Err(anyhow::Error::new(Cancelled { reason: None }).context("while crawling"))
+        });
+        let error = call(&registry, "stop", json!({})).unwrap_err();
+        assert!(error.chain().any(|cause| cause.is::<Cancelled>()));
+    }
+
+    #[test]
+    fn output_that_violates_the_output_schema_is_an_error_result() {
+        let mut registry = ToolRegistry::default();
+        let mut sum = tool("sum");
+        sum.output_schema = Some(schema_for!(Sum));
+        registry.register(sum, |_| {
+            Ok(CallToolResult {
+                structured_content: json!({"sum": "five"}).as_object().cloned(),
+                ..Default::default()
+            })
+        });
+        let result = call(&registry, "sum", json!({})).unwrap();
+        assert_eq!(result.is_error, Some(true));
+        let message = text(&result);
+        assert!(
+            message
+                .starts_with("tool 'sum' returned a result that does not match its output schema:"),
+            "{message}"
+        );
+        assert!(message.contains("- /sum:"), "{message}");
+    }
+
+    #[test]
+    fn missing_structured_content_violates_the_output_schema() {
+        let mut registry = ToolRegistry::default();
+        let mut sum = tool("sum");
+        sum.output_schema = Some(schema_for!(Sum));
+        registry.register(sum, |_| Ok(CallToolResult::default()));
+        let result = call(&registry, "sum", json!({})).unwrap();
+        assert_eq!(result.is_error, Some(true));
+        assert!(text(&result).contains("structured_content is missing"));
+    }
+
+    #[test]
+    #[should_panic(expected = "tool 'add' is already registered")]
+    fn duplicate_names_panic() {
+        registry().register(tool("add"), |_| Ok(CallToolResult::default()));
+    }
+}
@@ -296,6 +296,19 @@ impl CallToolResult {
     }
 }
 
+#[derive(Default, Debug, Clone, Serialize, Deserialize, FromBytes, ToBytes)]
+#[encoding(Json)]
+pub struct CancelledNotificationParam {
+    /// Optional reason for the cancellation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub reason: Option<String>,
+
+    /// The ID of the request to cancel
+    #[serde(rename = "requestId")]
+    pub request_id: PluginRequestId,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromBytes, ToBytes)]
 #[encoding(Json)]
 pub struct CompleteRequest {
@@ -845,6 +858,11 @@ pub struct KeyringEntryId {
 #[encoding(Json)]
 pub struct ListPromptsRequest {
     pub context: PluginRequestContext,
+
+    /// Opaque cursor from a previous result's `next_cursor`; absent for the first page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub cursor: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize, FromBytes, ToBytes)]
@@ -852,12 +870,23 @@ pub struct ListPromptsRequest {
 pub struct ListPromptsResult {
     /// Array of available prompts
     pub prompts: Vec<Prompt>,
+
+    /// Cursor for the next page of results, absent on the last page
+    #[serde(rename = "nextCursor")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize, FromBytes, ToBytes)]
 #[encoding(Json)]
 pub struct ListResourcesRequest {
     pub context: PluginRequestContext,
+
+    /// Opaque cursor from a previous result's `next_cursor`; absent for the first page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub cursor: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize, FromBytes, ToBytes)]
@@ -865,12 +894,23 @@ pub struct ListResourcesRequest {
 pub struct ListResourcesResult {
     /// Array of available resources
     pub resources: Vec<Resource>,
+
+    /// Cursor for the next page of results, absent on the last page
+    #[serde(rename = "nextCursor")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize, FromBytes, ToBytes)]
 #[encoding(Json)]
 pub struct ListResourceTemplatesRequest {
     pub context: PluginRequestContext,
+
+    /// Opaque cursor from a previous result's `next_cursor`; absent for the first page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub cursor: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize, FromBytes, ToBytes)]
@@ -879,6 +919,12 @@ pub struct ListResourceTemplatesResult {
     /// Array of resource templates
     #[serde(rename = "resourceTemplates")]
     pub resource_templates: Vec<ResourceTemplate>,
+
+    /// Cursor for the next page of results, absent on the last page
+    #[serde(rename = "nextCursor")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize, FromBytes, ToBytes)]
@@ -892,6 +938,11 @@ pub struct ListRootsResult {
 #[encoding(Json)]
 pub struct ListToolsRequest {
     pub context: PluginRequestContext,
+
+    /// Opaque cursor from a previous result's `next_cursor`; absent for the first page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub cursor: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize, FromBytes, ToBytes)]
@@ -899,9 +950,29 @@ pub struct ListToolsRequest {
 pub struct ListToolsResult {
     /// Array of available tools
     pub tools: Vec<Tool>,
+
+    /// Cursor for the next page of results, absent on the last page
+    #[serde(rename = "nextCursor")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub next_cursor: Option<String>,
 }
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize, FromBytes, ToBytes)]
+/// Severity of a log message, in increasing order.
+#[derive(
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    FromBytes,
+    ToBytes,
+)]
 #[encoding(Json)]
 pub enum LoggingLevel {
     #[default]
@@ -1045,7 +1116,7 @@ pub struct PluginRequestContext {
     pub id: PluginRequestId,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromBytes, ToBytes)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, FromBytes, ToBytes)]
 #[encoding(Json)]
 #[serde(untagged)]
 pub enum PluginRequestId {
@@ -1629,6 +1700,21 @@ impl<'de> Deserialize<'de> for Schema {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, FromBytes, ToBytes)]
+#[encoding(Json)]
+pub struct SetLevelRequest {
+    pub context: PluginRequestContext,
+
+    pub request: SetLevelRequestParam,
+}
+
+#[derive(Default, Debug, Clone, Serialize, Deserialize, FromBytes, ToBytes)]
+#[encoding(Json)]
+pub struct SetLevelRequestParam {
+    /// The minimum level of log messages the client wants to receive
+    pub level: LoggingLevel,
+}
+
 #[derive(Default, Debug, Clone, FromBytes, ToBytes)]
 #[encoding(Json)]
 pub struct StringSchema {
@@ -0,0 +1,1039 @@
+#![allow(unused)]
+use anyhow::{Result, anyhow};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+/// Characters allowed unencoded in every expansion (RFC 3986 `unreserved`).
+fn is_unreserved(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~')
+}
+
+/// Characters additionally allowed unencoded in `+` and `#` expansions (RFC 3986 `reserved`).
+fn is_reserved(c: char) -> bool {
+    matches!(
+        c,
+        ':' | '/'
+            | '?'
+            | '#'
+            | '['
+            | ']'
+            | '@'
+            | '!'
+            | '$'
+            | '&'
+            | '\''
+            | '('
+            | ')'
+            | '*'
+            | '+'
+            | ','
+            | ';'
+            | '='
+    )
+}
+
+/// The value bound to a template variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateValue {
+    String(String),
+    List(Vec<String>),
+    Map(Vec<(String, String)>),
+}
+
+impl TemplateValue {
+    /// Lists and maps without entries count as undefined (RFC 6570 section 2.3).
+    fn is_defined(&self) -> bool {
+        match self {
+            TemplateValue::String(_) => true,
+            TemplateValue::List(items) => !items.is_empty(),
+            TemplateValue::Map(pairs) => !pairs.is_empty(),
+        }
+    }
+}
+
+impl From<&str> for TemplateValue {
+    fn from(value: &str) -> Self {
+        TemplateValue::String(value.to_string())
+    }
+}
+
+impl From<String> for TemplateValue {
+    fn from(value: String) -> Self {
+        TemplateValue::String(value)
+    }
+}
+
+impl From<Vec<String>> for TemplateValue {
+    fn from(items: Vec<String>) -> Self {
+        TemplateValue::List(items)
+    }
+}
+
+impl From<Vec<&str>> for TemplateValue {
+    fn from(items: Vec<&str>) -> Self {
+        TemplateValue::List(items.into_iter().map(String::from).collect())
+    }
+}
+
+impl From<Vec<(String, String)>> for TemplateValue {
+    fn from(pairs: Vec<(String, String)>) -> Self {
+        TemplateValue::Map(pairs)
+    }
+}
+
+impl From<Vec<(&str, &str)>> for TemplateValue {
+    fn from(pairs: Vec<(&str, &str)>) -> Self {
+        TemplateValue::Map(
+            pairs
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        )
+    }
+}
+
+/// Variables to expand a template with, or extracted from a URI by matching it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Variables(BTreeMap<String, TemplateValue>);
+
+impl Variables {
+    /// Binds `name` to `value`, replacing any previous binding.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        value: impl Into<TemplateValue>,
+    ) -> &mut Self {
+        self.0.insert(name.into(), value.into());
+        self
+    }
+
+    /// Returns the value bound to `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&TemplateValue> {
+        self.0.get(name)
+    }
+
+    /// Returns the value bound to `name` if it is a single string.
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        match self.0.get(name)? {
+            TemplateValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Parses the single value bound to `name` into `T`.
+    ///
+    /// Fails if the variable is missing, is a list or map, or does not parse.
+    pub fn parse<T>(&self, name: &str) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let value = match self.0.get(name) {
+            Some(TemplateValue::String(s)) => s,
+            Some(_) => return Err(anyhow!("variable '{name}' must be a single value")),
+            None => return Err(anyhow!("missing variable '{name}'")),
+        };
+        value
+            .parse()
+            .map_err(|e| anyhow!("invalid value '{value}' for variable '{name}': {e}"))
+    }
+
+    /// Parses every item bound to `name` into `T`; a single string yields one item and a
+    /// missing variable yields none.
+    pub fn parse_list<T>(&self, name: &str) -> Result<Vec<T>>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let items = match self.0.get(name) {
+            Some(TemplateValue::String(s)) => vec![s.as_str()],
+            Some(TemplateValue::List(items)) => items.iter().map(String::as_str).collect(),
+            Some(TemplateValue::Map(_)) => {
+                return Err(anyhow!("variable '{name}' must be a list, not a map"));
+            }
+            None => Vec::new(),
+        };
+        items
+            .into_iter()
+            .map(|item| {
+                item.parse()
+                    .map_err(|e| anyhow!("invalid value '{item}' for variable '{name}': {e}"))
+            })
+            .collect()
+    }
+
+    /// Iterates over all bound variables in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TemplateValue)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Simple,
+    Reserved,
+    Fragment,
+    Label,
+    Path,
+    PathParam,
+    Query,
+    QueryContinuation,
+}
+
+impl Operator {
+    fn from_char(c: char) -> Option<Operator> {
+        Some(match c {
+            '+' => Operator::Reserved,
+            '#' => Operator::Fragment,
+            '.' => Operator::Label,
+            '/' => Operator::Path,
+            ';' => Operator::PathParam,
+            '?' => Operator::Query,
+            '&' => Operator::QueryContinuation,
+            _ => return None,
+        })
+    }
+
+    fn first(self) -> &'static str {
+        match self {
+            Operator::Simple | Operator::Reserved => "",
+            Operator::Fragment => "#",
+            Operator::Label => ".",
+            Operator::Path => "/",
+            Operator::PathParam => ";",
+            Operator::Query => "?",
+            Operator::QueryContinuation => "&",
+        }
+    }
+
+    fn separator(self) -> char {
+        match self {
+            Operator::Simple | Operator::Reserved | Operator::Fragment => ',',
+            Operator::Label => '.',
+            Operator::Path => '/',
+            Operator::PathParam => ';',
+            Operator::Query | Operator::QueryContinuation => '&',
+        }
+    }
+
+    fn named(self) -> bool {
+        matches!(
+            self,
+            Operator::PathParam | Operator::Query | Operator::QueryContinuation
+        )
+    }
+
+    fn query(self) -> bool {
+        matches!(self, Operator::Query | Operator::QueryContinuation)
+    }
+
+    fn if_empty(self) -> &'static str {
+        match self {
+            Operator::Query | Operator::QueryContinuation => "=",
+            _ => "",
+        }
+    }
+
+    fn allow_reserved(self) -> bool {
+        matches!(self, Operator::Reserved | Operator::Fragment)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Modifier {
+    None,
+    Prefix(usize),
+    Explode,
+}
+
+#[derive(Debug, Clone)]
+struct VarSpec {
+    name: String,
+    modifier: Modifier,
+}
+
+#[derive(Debug, Clone)]
+struct Expression {
+    operator: Operator,
+    vars: Vec<VarSpec>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    Expression(Expression),
+}
+
+/// An RFC 6570 URI template (levels 1 through 4).
+///
+/// Templates can be expanded into URIs, and URIs can be matched against a template to
+/// recover the variables that would have produced them. Matching is the inverse of
+/// expansion and is necessarily heuristic where expansion is lossy: for example an
+/// unexploded list and a string containing commas expand identically.
+#[derive(Debug, Clone)]
+pub struct UriTemplate {
+    source: String,
+    parts: Vec<Part>,
+}
+
+impl fmt::Display for UriTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl FromStr for UriTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(source: &str) -> Result<Self> {
+        UriTemplate::parse(source)
+    }
+}
+
+impl UriTemplate {
+    /// Parses a URI template.
+    pub fn parse(source: &str) -> Result<UriTemplate> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+
+        while !rest.is_empty() {
+            match rest.find(['{', '}']) {
+                Some(i) if rest[i..].starts_with('}') => {
+                    return Err(anyhow!("unmatched '}}' in URI template '{source}'"));
+                }
+                Some(start) => {
+                    if start > 0 {
+                        parts.push(Part::Literal(rest[..start].to_string()));
+                    }
+                    let end = rest[start..]
+                        .find('}')
+                        .map(|i| start + i)
+                        .ok_or_else(|| anyhow!("unclosed '{{' in URI template '{source}'"))?;
+                    parts.push(Part::Expression(parse_expression(
+                        &rest[start + 1..end],
+                        source,
+                    )?));
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    parts.push(Part::Literal(rest.to_string()));
+                    rest = "";
+                }
+            }
+        }
+
+        Ok(UriTemplate {
+            source: source.to_string(),
+            parts,
+        })
+    }
+
+    /// Returns the template source.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the names of all variables in the template, in order of appearance.
+    pub fn variable_names(&self) -> impl Iterator<Item = &str> {
+        self.parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Expression(expr) => Some(expr.vars.iter().map(|v| v.name.as_str())),
+                Part::Literal(_) => None,
+            })
+            .flatten()
+    }
+
+    /// Number of literal characters in the template, used to rank overlapping templates.
+    pub(crate) fn literal_len(&self) -> usize {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(literal) => literal.len(),
+                Part::Expression(_) => 0,
+            })
+            .sum()
+    }
+
+    /// Expands the template with `vars`; undefined variables are omitted.
+    pub fn expand(&self, vars: &Variables) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => out.push_str(literal),
+                Part::Expression(expr) => expand_expression(expr, vars, &mut out),
+            }
+        }
+        out
+    }
+
+    /// Matches `uri` against the template, returning the extracted variables on success.
+    ///
+    /// Query parameters that no `{?...}` or `{&...}` expression names are ignored, so
+    /// `x://items{?page}` matches `x://items?page=2&utm_source=mail`.
+    pub fn match_uri(&self, uri: &str) -> Option<Variables> {
+        let mut vars = Variables::default();
+        match_parts(&self.parts, 0, uri, &mut vars, &mut HashSet::new()).then_some(vars)
+    }
+}
+
+fn parse_expression(body: &str, source: &str) -> Result<Expression> {
+    let mut chars = body.chars();
+    let operator = match chars.clone().next() {
+        Some(c) if "=,!@|".contains(c) => {
+            return Err(anyhow!(
+                "reserved operator '{c}' in URI template '{source}'"
+            ));
+        }
+        Some(c) => Operator::from_char(c).inspect(|_| {
+            chars.next();
+        }),
+        None => return Err(anyhow!("empty expression in URI template '{source}'")),
+    }
+    .unwrap_or(Operator::Simple);
+
+    let vars = chars
+        .as_str()
+        .split(',')
+        .map(|spec| {
+            let (name, modifier) = if let Some(name) = spec.strip_suffix('*') {
+                (name, Modifier::Explode)
+            } else if let Some((name, len)) = spec.split_once(':') {
+                let len = len
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|len| (1..10000).contains(len))
+                    .ok_or_else(|| {
+                        anyhow!("invalid prefix length in '{spec}' in URI template '{source}'")
+                    })?;
+                (name, Modifier::Prefix(len))
+            } else {
+                (spec, Modifier::None)
+            };
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '%'))
+            {
+                return Err(anyhow!(
+                    "invalid variable name '{name}' in URI template '{source}'"
+                ));
+            }
+            Ok(VarSpec {
+                name: name.to_string(),
+                modifier,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Expression { operator, vars })
+}
+
+pub(crate) fn encode(value: &str, allow_reserved: bool, out: &mut String) {
+    let bytes = value.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        if bytes[i].is_ascii() && (is_unreserved(c) || (allow_reserved && is_reserved(c))) {
+            out.push(c);
+        } else if allow_reserved
+            && c == '%'
+            && bytes
+                .get(i + 1..i + 3)
+                .is_some_and(|h| h.iter().all(u8::is_ascii_hexdigit))
+        {
+            // Reserved expansion passes existing pct-encoded triplets through untouched.
+            out.push_str(&value[i..i + 3]);
+            i += 2;
+        } else {
+            out.push_str(&format!("%{:02X}", bytes[i]));
+        }
+        i += 1;
+    }
+}
+
+fn expand_expression(expr: &Expression, vars: &Variables, out: &mut String) {
+    let op = expr.operator;
+    let allow = op.allow_reserved();
+    let mut first = true;
+
+    for spec in &expr.vars {
+        let Some(value) = vars.get(&spec.name).filter(|v| v.is_defined()) else {
+            continue;
+        };
+        out.push_str(if first { op.first() } else { "" });
+        if !first {
+            out.push(op.separator());
+        }
+        first = false;
+
+        let push_named = |out: &mut String, name: &str, value: &str| {
+            encode(name, false, out);
+            if value.is_empty() {
+                out.push_str(op.if_empty());
+            } else {
+                out.push('=');
+                encode(value, allow, out);
+            }
+        };
+
+        match value {
+            TemplateValue::String(s) => {
+                let s = match spec.modifier {
+                    Modifier::Prefix(len) => s.chars().take(len).collect(),
+                    _ => s.clone(),
+                };
+                if op.named() {
+                    push_named(out, &spec.name, &s);
+                } else {
+                    encode(&s, allow, out);
+                }
+            }
+            TemplateValue::List(items) if spec.modifier == Modifier::Explode => {
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(op.separator());
+                    }
+                    if op.named() {
+                        push_named(out, &spec.name, item);
+                    } else {
+                        encode(item, allow, out);
+                    }
+                }
+            }
+            TemplateValue::Map(pairs) if spec.modifier == Modifier::Explode => {
+                for (i, (k, v)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        out.push(op.separator());
+                    }
+                    if op.named() {
+                        push_named(out, k, v);
+                    } else {
+                        encode(k, allow, out);
+                        out.push('=');
+                        encode(v, allow, out);
+                    }
+                }
+            }
+            TemplateValue::List(_) | TemplateValue::Map(_) => {
+                if op.named() {
+                    encode(&spec.name, false, out);
+                    out.push('=');
+                }
+                let flat: Vec<&String> = match value {
+                    TemplateValue::List(items) => items.iter().collect(),
+                    TemplateValue::Map(pairs) => pairs.iter().flat_map(|(k, v)| [k, v]).collect(),
+                    TemplateValue::String(_) => unreachable!(),
+                };
+                for (i, item) in flat.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    encode(item, allow, out);
+                }
+            }
+        }
+    }
+}
+
+fn decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Matches the URI suffix `uri` against `parts[index..]`.
+///
+/// Whether a suffix matches does not depend on the variables bound before it, so each
+/// `(index, suffix length)` that failed is remembered in `failed` and never tried again.
+/// This bounds the backtracking over adjacent expressions such as `{a}{b}{c}` to
+/// polynomial time.
+fn match_parts(
+    parts: &[Part],
+    index: usize,
+    uri: &str,
+    vars: &mut Variables,
+    failed: &mut HashSet<(usize, usize)>,
+) -> bool {
+    let Some(part) = parts.get(index) else {
+        return uri.is_empty();
+    };
+    if failed.contains(&(index, uri.len())) {
+        return false;
+    }
+
+    let matched = match part {
+        Part::Literal(literal) => uri
+            .strip_prefix(literal.as_str())
+            .is_some_and(|uri| match_parts(parts, index + 1, uri, vars, failed)),
+        Part::Expression(expr) => {
+            // Query parameters named by a later expression must be left for it.
+            let claimed: Vec<&str> = parts[index + 1..]
+                .iter()
+                .filter_map(|part| match part {
+                    Part::Expression(later) if later.operator.query() => Some(&later.vars),
+                    _ => None,
+                })
+                .flatten()
+                .map(|spec| spec.name.as_str())
+                .collect();
+            // Try the longest candidate first so that greedy variables like `{+path}` take
+            // as much as the remaining template allows.
+            let next_literal = match parts.get(index + 1) {
+                Some(Part::Literal(literal)) => Some(literal.as_str()),
+                _ => None,
+            };
+            (0..=uri.len())
+                .rev()
+                .filter(|&end| uri.is_char_boundary(end))
+                .filter(|&end| next_literal.is_none_or(|literal| uri[end..].starts_with(literal)))
+                .any(|end| {
+                    let Some(matched) = match_expression(expr, &uri[..end], &claimed) else {
+                        return false;
+                    };
+                    let mut candidate = vars.clone();
+                    for (name, value) in matched {
+                        candidate.insert(name, value);
+                    }
+                    let matched =
+                        match_parts(parts, index + 1, &uri[end..], &mut candidate, failed);
+                    if matched {
+                        *vars = candidate;
+                    }
+                    matched
+                })
+        }
+    };
+    if !matched {
+        failed.insert((index, uri.len()));
+    }
+    matched
+}
+
+/// Matches the text `s` produced by one expression, leaving the `claimed` query
+/// parameters to later expressions.
+fn match_expression(
+    expr: &Expression,
+    s: &str,
+    claimed: &[&str],
+) -> Option<Vec<(String, TemplateValue)>> {
+    let op = expr.operator;
+    if s.is_empty() {
+        // Prefixed expressions vanish when all their variables are undefined; bare ones
+        // must match something, or `/users/{id}` would match `/users/`.
+        return (!op.first().is_empty()).then(Vec::new);
+    }
+    let body = s.strip_prefix(op.first())?;
+    let sep = op.separator();
+    let exploded = expr.vars.iter().any(|v| v.modifier == Modifier::Explode);
+
+    let valid = body.chars().all(|c| {
+        is_unreserved(c)
+            || c == '%'
+            || c == ','
+            || c == sep
+            || (c == '=' && (op.named() || exploded))
+            || (op.allow_reserved() && is_reserved(c))
+            || (op.query() && is_reserved(c) && c != '#')
+    });
+    if !valid || body.is_empty() && !op.named() {
+        return None;
+    }
+
+    let items: Vec<&str> = body.split(sep).collect();
+    let mut matched = Vec::new();
+
+    if op.named() {
+        let mut pairs = Vec::new();
+        for item in items {
+            let (k, v) = item.split_once('=').unwrap_or((item, ""));
+            pairs.push((decode(k)?, v));
+        }
+
+        let explode_spec = expr.vars.iter().find(|v| v.modifier == Modifier::Explode);
+        let mut leftovers = Vec::new();
+        for spec in &expr.vars {
+            let values: Vec<&str> = pairs
+                .iter()
+                .filter(|(k, _)| *k == spec.name)
+                .map(|(_, v)| *v)
+                .collect();
+            match values.as_slice() {
+                [] => {}
+                [value] => matched.push((spec.name.clone(), unexploded(spec, op, value)?)),
+                _ if spec.modifier == Modifier::Explode => matched.push((
+                    spec.name.clone(),
+                    TemplateValue::List(values.iter().map(|v| decode(v)).collect::<Option<_>>()?),
+                )),
+                _ => return None,
+            }
+        }
+        for (k, v) in &pairs {
+            if expr.vars.iter().any(|spec| spec.name == *k) {
+                continue;
+            }
+            if claimed.contains(&k.as_str()) {
+                return None;
+            }
+            if explode_spec.is_some() {
+                leftovers.push((k.clone(), decode(v)?));
+            } else if !op.query() {
+                return None;
+            }
+            // Other query parameters, such as tracking parameters, are ignored.
+        }
+        if let Some(spec) = explode_spec
+            && !leftovers.is_empty()
+        {
+            // Keys that name no variable are the entries of an exploded map.
+            matched.push((spec.name.clone(), TemplateValue::Map(leftovers)));
+        }
+        return Some(matched);
+    }
+
+    if let [spec] = expr.vars.as_slice() {
+        let value = if spec.modifier == Modifier::Explode {
+            if items.iter().all(|item| item.contains('=')) {
+                TemplateValue::Map(
+                    items
+                        .iter()
+                        .map(|item| {
+                            let (k, v) = item.split_once('=')?;
+                            Some((decode(k)?, decode(v)?))
+                        })
+                        .collect::<Option<_>>()?,
+                )
+            } else {
+                TemplateValue::List(items.iter().map(|v| decode(v)).collect::<Option<_>>()?)
+            }
+        } else {
+            unexploded(spec, op, body)?
+        };
+        return Some(vec![(spec.name.clone(), value)]);
+    }
+
+    if items.len() > expr.vars.len() {
+        return None;
+    }
+    for (spec, item) in expr.vars.iter().zip(items) {
+        let value = decode(item)?;
+        if let Modifier::Prefix(len) = spec.modifier
+            && value.chars().count() > len
+        {
+            return None;
+        }
+        matched.push((spec.name.clone(), TemplateValue::String(value)));
+    }
+    Some(matched)
+}
+
+/// Interprets the raw text of a single unexploded variable.
+fn unexploded(spec: &VarSpec, op: Operator, raw: &str) -> Option<TemplateValue> {
+    // Outside reserved expansion a literal comma can only come from joining list items.
+    if !op.allow_reserved() && raw.contains(',') && !matches!(spec.modifier, Modifier::Prefix(_)) {
+        return Some(TemplateValue::List(
+            raw.split(',').map(decode).collect::<Option<_>>()?,
+        ));
+    }
+    let value = decode(raw)?;
+    if let Modifier::Prefix(len) = spec.modifier
+        && value.chars().count() > len
+    {
+        return None;
+    }
+    Some(TemplateValue::String(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The variables of the examples in RFC 6570 section 3.2.
+    fn rfc_variables() -> Variables {
+        let mut vars = Variables::default();
+        vars.insert("var", "value")
+            .insert("hello", "Hello World!")
+            .insert("path", "/foo/bar")
+            .insert("empty", "")
+            .insert("x", "1024")
+            .insert("y", "768")
+            .insert("list", vec!["red", "green", "blue"])
+            .insert("keys", vec![("semi", ";"), ("dot", "."), ("comma", ",")])
+            .insert("empty_keys", Vec::<(&str, &str)>::new());
+        vars
+    }
+
+    fn assert_expansions(cases: &[(&str, &str)]) {
+        let vars = rfc_variables();
+        for (template, expected) in cases {
+            let template = UriTemplate::parse(template).unwrap();
+            assert_eq!(template.expand(&vars), *expected, "expanding {template}");
+        }
+    }
+
+    fn matched(template: &str, uri: &str) -> Option<Variables> {
+        UriTemplate::parse(template).unwrap().match_uri(uri)
+    }
+
+    #[test]
+    fn expands_level_1() {
+        assert_expansions(&[("{var}", "value"), ("{hello}", "Hello%20World%21")]);
+    }
+
+    #[test]
+    fn expands_level_2() {
+        assert_expansions(&[
+            ("{+var}", "value"),
+            ("{+hello}", "Hello%20World!"),
+            ("{+path}/here", "/foo/bar/here"),
+            ("here?ref={+path}", "here?ref=/foo/bar"),
+            ("X{#var}", "X#value"),
+            ("X{#hello}", "X#Hello%20World!"),
+        ]);
+    }
+
+    #[test]
+    fn expands_level_3() {
+        assert_expansions(&[
+            ("map?{x,y}", "map?1024,768"),
+            ("{x,hello,y}", "1024,Hello%20World%21,768"),
+            ("{+x,hello,y}", "1024,Hello%20World!,768"),
+            ("{+path,x}/here", "/foo/bar,1024/here"),
+            ("{#x,hello,y}", "#1024,Hello%20World!,768"),
+            ("X{.var}", "X.value"),
+            ("X{.x,y}", "X.1024.768"),
+            ("{/var}", "/value"),
+            ("{/var,x}/here", "/value/1024/here"),
+            ("{;x,y}", ";x=1024;y=768"),
+            ("{;x,y,empty}", ";x=1024;y=768;empty"),
+            ("{?x,y}", "?x=1024&y=768"),
+            ("{?x,y,empty}", "?x=1024&y=768&empty="),
+            ("?fixed=yes{&x}", "?fixed=yes&x=1024"),
+            ("{&x,y,empty}", "&x=1024&y=768&empty="),
+        ]);
+    }
+
+    #[test]
+    fn expands_level_4() {
+        assert_expansions(&[
+            ("{var:3}", "val"),
+            ("{var:30}", "value"),
+            ("{list}", "red,green,blue"),
+            ("{list*}", "red,green,blue"),
+            ("{keys}", "semi,%3B,dot,.,comma,%2C"),
+            ("{keys*}", "semi=%3B,dot=.,comma=%2C"),
+            ("{+path:6}/here", "/foo/b/here"),
+            ("{+list}", "red,green,blue"),
+            ("{+keys}", "semi,;,dot,.,comma,,"),
+            ("{+keys*}", "semi=;,dot=.,comma=,"),
+            ("{#path:6}/here", "#/foo/b/here"),
+            ("{#list*}", "#red,green,blue"),
+            ("{#keys*}", "#semi=;,dot=.,comma=,"),
+            ("X{.var:3}", "X.val"),
+            ("X{.list}", "X.red,green,blue"),
+            ("X{.list*}", "X.red.green.blue"),
+            ("X{.keys*}", "X.semi=%3B.dot=..comma=%2C"),
+            ("{/var:1,var}", "/v/value"),
+            ("{/list*}", "/red/green/blue"),
+            ("{/list*,path:4}", "/red/green/blue/%2Ffoo"),
+            ("{/keys*}", "/semi=%3B/dot=./comma=%2C"),
+            ("{;hello:5}", ";hello=Hello"),
+            ("{;list}", ";list=red,green,blue"),
+            ("{;list*}", ";list=red;list=green;list=blue"),
+            ("{;keys*}", ";semi=%3B;dot=.;comma=%2C"),
+            ("{?var:3}", "?var=val"),
+            ("{?list}", "?list=red,green,blue"),
+            ("{?list*}", "?list=red&list=green&list=blue"),
+            ("{?keys*}", "?semi=%3B&dot=.&comma=%2C"),
+            ("{&var:3}", "&var=val"),
+            ("{&list*}", "&list=red&list=green&list=blue"),
+            ("{&keys*}", "&semi=%3B&dot=.&comma=%2C"),
+        ]);
+    }
+
+    #[test]
+    fn undefined_variables_are_omitted() {
+        assert_expansions(&[
+            ("{undef}", ""),
+            ("{x,undef,y}", "1024,768"),
+            ("{?undef}", ""),
+            ("{?x,undef}", "?x=1024"),
+            ("{/empty_keys*}", ""),
+            ("X{.undef}Y", "XY"),
+        ]);
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for template in [
+            "{var", "var}", "{}", "{=var}", "{var:0}", "{var:x}", "{va r}",
+        ] {
+            assert!(UriTemplate::parse(template).is_err(), "{template}");
+        }
+    }
+
+    #[test]
+    fn matches_simple_and_reserved_expressions() {
+        let vars = matched("users/{id}", "users/42").unwrap();
+        assert_eq!(vars.get_str("id"), Some("42"));
+        assert_eq!(vars.parse::<u32>("id").unwrap(), 42);
+        assert!(matched("users/{id}", "users/").is_none());
+        assert!(matched("users/{id}", "users/4/2").is_none());
+
+        let vars = matched("greet/{hello}", "greet/Hello%20World%21").unwrap();
+        assert_eq!(vars.get_str("hello"), Some("Hello World!"));
+
+        let vars = matched("file://{+path}/here", "file:///foo/bar/here").unwrap();
+        assert_eq!(vars.get_str("path"), Some("/foo/bar"));
+
+        let vars = matched("doc{#section}", "doc#intro/usage").unwrap();
+        assert_eq!(vars.get_str("section"), Some("intro/usage"));
+    }
+
+    #[test]
+    fn matches_multiple_variable_expressions() {
+        let vars = matched("map?{x,y}", "map?1024,768").unwrap();
+        assert_eq!(vars.get_str("x"), Some("1024"));
+        assert_eq!(vars.get_str("y"), Some("768"));
+
+        let vars = matched("X{.x,y}", "X.1024.768").unwrap();
+        assert_eq!(vars.get_str("y"), Some("768"));
+
+        let vars = matched("{/var,x}/here", "/value/1024/here").unwrap();
+        assert_eq!(vars.get_str("var"), Some("value"));
+        assert_eq!(vars.get_str("x"), Some("1024"));
+
+        let vars = matched("item{;x,y}", "item;x=1024;y=768").unwrap();
+        assert_eq!(vars.get_str("x"), Some("1024"));
+
+        let vars = matched("search{?q,page}", "search?q=rust%20mcp&page=2").unwrap();
+        assert_eq!(vars.get_str("q"), Some("rust mcp"));
+        assert_eq!(vars.get_str("page"), Some("2"));
+    }
+
+    #[test]
+    fn matches_lists_maps_and_prefixes() {
+        let vars = matched("tags/{tags}", "tags/red,green,blue").unwrap();
+        assert_eq!(
+            vars.parse_list::<String>("tags").unwrap(),
+            ["red", "green", "blue"]
+        );
+
+        let vars = matched("x:{/list*}", "x:/red/green/blue").unwrap();
+        assert_eq!(
+            vars.get("list"),
+            Some(&TemplateValue::from(vec!["red", "green", "blue"]))
+        );
+
+        let vars = matched("x:{?list*}", "x:?list=red&list=green").unwrap();
+        assert_eq!(
+            vars.get("list"),
+            Some(&TemplateValue::from(vec!["red", "green"]))
+        );
+
+        let vars = matched("x:{?keys*}", "x:?semi=%3B&dot=.&comma=%2C").unwrap();
+        assert_eq!(
+            vars.get("keys"),
+            Some(&TemplateValue::from(vec![
+                ("semi", ";"),
+                ("dot", "."),
+                ("comma", ",")
+            ]))
+        );
+
+        let vars = matched("x:{keys*}", "x:a=1,b=2").unwrap();
+        assert_eq!(
+            vars.get("keys"),
+            Some(&TemplateValue::from(vec![("a", "1"), ("b", "2")]))
+        );
+
+        assert!(matched("code/{var:3}", "code/val").is_some());
+        assert!(matched("code/{var:3}", "code/value").is_none());
+    }
+
+    #[test]
+    fn query_parameters_match_in_any_order_and_may_be_absent() {
+        let vars = matched("x://items{?page,sort}", "x://items?sort=name&page=3").unwrap();
+        assert_eq!(vars.get_str("page"), Some("3"));
+        assert_eq!(vars.get_str("sort"), Some("name"));
+
+        let vars = matched("x://items{?page,sort}", "x://items").unwrap();
+        assert_eq!(vars.get("page"), None);
+        assert!(matched("x://items{?page}", "x://items?page=1&page=2").is_none());
+    }
+
+    #[test]
+    fn unknown_query_parameters_are_ignored() {
+        let vars = matched("x://{?page}", "x://?page=2&utm=1").unwrap();
+        assert_eq!(vars.get_str("page"), Some("2"));
+        assert_eq!(vars.get("utm"), None);
+
+        let vars = matched("x://{?page}", "x://?utm=a+b/c&page=2").unwrap();
+        assert_eq!(vars.get_str("page"), Some("2"));
+
+        let vars = matched("x://{?page}", "x://?utm=1").unwrap();
+        assert_eq!(vars.get("page"), None);
+
+        // Unknown parameters are still not allowed outside of query expressions.
+        assert!(matched("x://item{;x}", "x://item;x=1;y=2").is_none());
+    }
+
+    #[test]
+    fn query_parameters_named_later_are_left_to_their_expression() {
+        let vars = matched("x://{?a}{&b}", "x://?a=1&utm=2&b=3").unwrap();
+        assert_eq!(vars.get_str("a"), Some("1"));
+        assert_eq!(vars.get_str("b"), Some("3"));
+
+        let vars = matched("x://{?a}{&b}", "x://?utm=2&a=1&b=3").unwrap();
+        assert_eq!(vars.get_str("a"), Some("1"));
+        assert_eq!(vars.get_str("b"), Some("3"));
+
+        let vars = matched("x://{?filters*}{&page}", "x://?lang=en&page=2").unwrap();
+        assert_eq!(
+            vars.get("filters"),
+            Some(&TemplateValue::from(vec![("lang", "en")]))
+        );
+        assert_eq!(vars.get_str("page"), Some("2"));
+    }
+
+    #[test]
+    fn expansion_round_trips_through_matching() {
+        let template =
+            UriTemplate::parse("repo://{owner}/{repo}/issues{/number}{?state,labels*}").unwrap();
+        let mut vars = Variables::default();
+        vars.insert("owner", "hyper mcp")
+            .insert("repo", "plugins")
+            .insert("number", "7")
+            .insert("state", "open")
+            .insert("labels", vec!["bug", "good first issue"]);
+        let uri = template.expand(&vars);
+        assert_eq!(
+            uri,
+            "repo://hyper%20mcp/plugins/issues/7?state=open&labels=bug&labels=good%20first%20issue"
+        );
+        assert_eq!(template.match_uri(&uri), Some(vars));
+    }
+
+    #[test]
+    fn backtracking_over_adjacent_expressions_is_bounded() {
+        let template = UriTemplate::parse("{a}{b}{c}{d}{e}{f}{g}{h}!").unwrap();
+        assert!(template.match_uri(&"x".repeat(64)).is_none());
+
+        let vars = template.match_uri(&format!("{}!", "x".repeat(64))).unwrap();
+        assert_eq!(vars.iter().count(), 8);
+    }
+}
@@ -0,0 +1,688 @@
+#![allow(unused)]
+use chrono::{DateTime, NaiveDate};
+use schemars::Schema;
+use serde_json::{Map, Value};
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+/// Maximum number of `$ref` hops followed without descending into the instance.
+const MAX_REF_DEPTH: usize = 64;
+
+/// A single way in which a JSON value fails to conform to a schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// JSON pointer to the offending value (empty for the root value)
+    pub path: String,
+
+    /// Human-readable description of the violation
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Validates `instance` against `schema`, returning every violation found.
+///
+/// Supports the JSON Schema keywords that tool schemas use in practice: `type`, `enum`,
+/// `const`, numeric and length bounds, `format`, `properties`, `required`,
+/// `additionalProperties`, array `items`, the `allOf`/`anyOf`/`oneOf`/`not` combinators,
+/// `if`/`then`/`else` and local `$ref`s. Unknown keywords and formats are ignored, as are
+/// keywords that need a regex engine (`pattern`, `patternProperties`).
+pub fn validate(schema: &Schema, instance: &Value) -> Vec<Violation> {
+    let mut validator = Validator {
+        root: schema.as_value(),
+        violations: Vec::new(),
+    };
+    validator.check(schema.as_value(), instance, "", 0);
+    validator.violations
+}
+
+/// Returns `true` if `instance` conforms to `schema`.
+pub fn is_valid(schema: &Schema, instance: &Value) -> bool {
+    validate(schema, instance).is_empty()
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+    violations: Vec<Violation>,
+}
+
+impl<'a> Validator<'a> {
+    fn report(&mut self, path: &str, message: impl Into<String>) {
+        self.violations.push(Violation {
+            path: path.to_string(),
+            message: message.into(),
+        });
+    }
+
+    /// Runs `schema` against `instance` in a scratch validator and returns its violations.
+    fn probe(&self, schema: &Value, instance: &Value, path: &str, depth: usize) -> Vec<Violation> {
+        let mut probe = Validator {
+            root: self.root,
+            violations: Vec::new(),
+        };
+        probe.check(schema, instance, path, depth);
+        probe.violations
+    }
+
+    fn resolve(&self, reference: &str) -> Option<&'a Value> {
+        let pointer = reference.strip_prefix('#')?;
+        if pointer.is_empty() {
+            Some(self.root)
+        } else {
+            self.root.pointer(pointer)
+        }
+    }
+
+    fn check(&mut self, schema: &Value, instance: &Value, path: &str, depth: usize) {
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => return self.report(path, "no value is allowed here"),
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str)
+            && depth < MAX_REF_DEPTH
+            && let Some(target) = self.resolve(reference)
+        {
+            self.check(target, instance, path, depth + 1);
+        }
+
+        if let Some(expected) = schema.get("type")
+            && !type_matches(expected, instance)
+        {
+            return self.report(
+                path,
+                format!(
+                    "expected {}, found {}",
+                    describe_type(expected),
+                    type_name(instance)
+                ),
+            );
+        }
+
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+            && !allowed.iter().any(|v| json_eq(v, instance))
+        {
+            self.report(
+                path,
+                format!("must be one of {}", Value::from(allowed.clone())),
+            );
+        }
+
+        if let Some(expected) = schema.get("const")
+            && !json_eq(expected, instance)
+        {
+            self.report(path, format!("must be {expected}"));
+        }
+
+        match instance {
+            Value::Number(n) => {
+                if let Some(n) = n.as_f64() {
+                    self.check_number(schema, n, path);
+                }
+            }
+            Value::String(s) => self.check_string(schema, s, path),
+            Value::Array(items) => self.check_array(schema, items, path),
+            Value::Object(object) => self.check_object(schema, object, path),
+            _ => {}
+        }
+
+        self.check_combinators(schema, instance, path, depth);
+    }
+
+    fn check_number(&mut self, schema: &Map<String, Value>, n: f64, path: &str) {
+        let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+
+        if let Some(min) = bound("minimum")
+            && n < min
+        {
+            self.report(path, format!("must be >= {min}"));
+        }
+        if let Some(max) = bound("maximum")
+            && n > max
+        {
+            self.report(path, format!("must be <= {max}"));
+        }
+        if let Some(min) = bound("exclusiveMinimum")
+            && n <= min
+        {
+            self.report(path, format!("must be > {min}"));
+        }
+        if let Some(max) = bound("exclusiveMaximum")
+            && n >= max
+        {
+            self.report(path, format!("must be < {max}"));
+        }
+        if let Some(divisor) = bound("multipleOf")
+            && divisor > 0.0
+            && (n / divisor).fract().abs() > f64::EPSILON
+        {
+            self.report(path, format!("must be a multiple of {divisor}"));
+        }
+    }
+
+    fn check_string(&mut self, schema: &Map<String, Value>, s: &str, path: &str) {
+        let len = s.chars().count() as u64;
+
+        if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+            && len < min
+        {
+            self.report(path, format!("must be at least {min} characters long"));
+        }
+        if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+            && len > max
+        {
+            self.report(path, format!("must be at most {max} characters long"));
+        }
+        if let Some(format) = schema.get("format").and_then(Value::as_str)
+            && format_matches(format, s) == Some(false)
+        {
+            self.report(path, format!("is not a valid {format}"));
+        }
+    }
+
+    fn check_array(&mut self, schema: &Map<String, Value>, items: &[Value], path: &str) {
+        let len = items.len() as u64;
+
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+            && len < min
+        {
+            self.report(path, format!("must contain at least {min} items"));
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+            && len > max
+        {
+            self.report(path, format!("must contain at most {max} items"));
+        }
+        if schema.get("uniqueItems") == Some(&Value::Bool(true))
+            && let Some(i) =
+                (1..items.len()).find(|&i| items[..i].iter().any(|v| json_eq(v, &items[i])))
+        {
+            self.report(&pointer(path, &i.to_string()), "duplicates an earlier item");
+        }
+
+        let prefix = schema
+            .get("prefixItems")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for (i, item) in items.iter().enumerate() {
+            let item_path = pointer(path, &i.to_string());
+            if let Some(item_schema) = prefix.get(i) {
+                self.check(item_schema, item, &item_path, 0);
+            } else if let Some(item_schema) = schema.get("items") {
+                self.check(item_schema, item, &item_path, 0);
+            }
+        }
+    }
+
+    fn check_object(
+        &mut self,
+        schema: &Map<String, Value>,
+        object: &Map<String, Value>,
+        path: &str,
+    ) {
+        let len = object.len() as u64;
+
+        if let Some(min) = schema.get("minProperties").and_then(Value::as_u64)
+            && len < min
+        {
+            self.report(path, format!("must have at least {min} properties"));
+        }
+        if let Some(max) = schema.get("maxProperties").and_then(Value::as_u64)
+            && len > max
+        {
+            self.report(path, format!("must have at most {max} properties"));
+        }
+
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(name) {
+                    self.report(path, format!("missing required property '{name}'"));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        // Without a regex engine we cannot tell which keys `patternProperties` covers, so
+        // `additionalProperties` is only enforced when there are none.
+        let additional = schema
+            .get("additionalProperties")
+            .filter(|_| !schema.contains_key("patternProperties"));
+
+        for (key, value) in object {
+            let value_path = pointer(path, key);
+            match properties.and_then(|p| p.get(key)) {
+                Some(property_schema) => self.check(property_schema, value, &value_path, 0),
+                None => match additional {
+                    Some(Value::Bool(false)) => {
+                        self.report(&value_path, "is not an allowed property")
+                    }
+                    Some(additional) => self.check(additional, value, &value_path, 0),
+                    None => {}
+                },
+            }
+        }
+    }
+
+    fn check_combinators(
+        &mut self,
+        schema: &Map<String, Value>,
+        instance: &Value,
+        path: &str,
+        depth: usize,
+    ) {
+        if let Some(all_of) = schema.get("allOf").and_then(Value::as_array) {
+            for sub in all_of {
+                self.check(sub, instance, path, depth + 1);
+            }
+        }
+
+        if let Some(any_of) = schema.get("anyOf").and_then(Value::as_array) {
+            let mut failures: Vec<_> = any_of
+                .iter()
+                .map(|sub| self.probe(sub, instance, path, depth + 1))
+                .collect();
+            if failures.iter().all(|f| !f.is_empty()) {
+                self.report_no_match(path, &mut failures);
+            }
+        }
+
+        if let Some(one_of) = schema.get("oneOf").and_then(Value::as_array) {
+            let mut failures: Vec<_> = one_of
+                .iter()
+                .map(|sub| self.probe(sub, instance, path, depth + 1))
+                .collect();
+            match failures.iter().filter(|f| f.is_empty()).count() {
+                0 => self.report_no_match(path, &mut failures),
+                1 => {}
+                n => self.report(
+                    path,
+                    format!("matches {n} of the allowed schemas, expected exactly one"),
+                ),
+            }
+        }
+
+        if let Some(not) = schema.get("not")
+            && self.probe(not, instance, path, depth + 1).is_empty()
+        {
+            self.report(path, "matches a schema it must not match");
+        }
+
+        if let Some(condition) = schema.get("if") {
+            let branch = if self.probe(condition, instance, path, depth + 1).is_empty() {
+                schema.get("then")
+            } else {
+                schema.get("else")
+            };
+            if let Some(branch) = branch {
+                self.check(branch, instance, path, depth + 1);
+            }
+        }
+    }
+
+    /// Reports an `anyOf`/`oneOf` mismatch.
+    ///
+    /// When exactly one alternative got past the type check (for example the non-null branch
+    /// of an `Option`), its violations are reported directly since they are the most useful.
+    fn report_no_match(&mut self, path: &str, failures: &mut [Vec<Violation>]) {
+        let mut plausible = failures.iter_mut().filter(|f| {
+            !(f.len() == 1 && f[0].path == path && f[0].message.starts_with("expected "))
+        });
+        if let (Some(only), None) = (plausible.next(), plausible.next()) {
+            self.violations.append(only);
+        } else {
+            self.report(path, "does not match any of the allowed schemas");
+        }
+    }
+}
+
+/// Appends `token` to the JSON pointer `path`, escaping it per RFC 6901.
+fn pointer(path: &str, token: &str) -> String {
+    format!("{path}/{}", token.replace('~', "~0").replace('/', "~1"))
+}
+
+fn type_matches(expected: &Value, instance: &Value) -> bool {
+    match expected {
+        Value::String(t) => is_type(t, instance),
+        Value::Array(types) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .any(|t| is_type(t, instance)),
+        _ => true,
+    }
+}
+
+fn is_type(t: &str, instance: &Value) -> bool {
+    match (t, instance) {
+        ("null", Value::Null)
+        | ("boolean", Value::Bool(_))
+        | ("object", Value::Object(_))
+        | ("array", Value::Array(_))
+        | ("number", Value::Number(_))
+        | ("string", Value::String(_)) => true,
+        ("integer", Value::Number(n)) => {
+            n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        _ => false,
+    }
+}
+
+fn describe_type(expected: &Value) -> String {
+    match expected {
+        Value::Array(types) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" or "),
+        Value::String(t) => t.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn type_name(instance: &Value) -> &'static str {
+    match instance {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Compares two JSON values, treating numerically equal integers and floats as equal.
+fn json_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a == b || a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| json_eq(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(k, v)| b.get(k).is_some_and(|other| json_eq(v, other)))
+        }
+        _ => a == b,
+    }
+}
+
+/// Checks `s` against a string `format`, returning `None` for formats that are not checked.
+fn format_matches(format: &str, s: &str) -> Option<bool> {
+    Some(match format {
+        "date-time" | "date_time" => DateTime::parse_from_rfc3339(s).is_ok(),
+        "date" => NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok(),
+        "time" => DateTime::parse_from_rfc3339(&format!("1970-01-01T{s}")).is_ok(),
+        "email" => s.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !domain.contains('@')
+                && !s.chars().any(char::is_whitespace)
+        }),
+        "uri" => s.split_once(':').is_some_and(|(scheme, _)| {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+                && !s.chars().any(|c| c.is_whitespace() || c.is_control())
+        }),
+        "uuid" => {
+            let groups: Vec<_> = s.split('-').collect();
+            groups.iter().map(|g| g.len()).eq([8, 4, 4, 4, 12])
+                && groups
+                    .iter()
+                    .all(|g| g.chars().all(|c| c.is_ascii_hexdigit()))
+        }
+        "ipv4" => s.parse::<Ipv4Addr>().is_ok(),
+        "ipv6" => s.parse::<Ipv6Addr>().is_ok(),
+        "hostname" => {
+            !s.is_empty()
+                && s.len() <= 253
+                && s.split('.').all(|label| {
+                    !label.is_empty()
+                        && label.len() <= 63
+                        && !label.starts_with('-')
+                        && !label.ends_with('-')
+                        && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                })
+        }
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn violations(schema: Value, instance: Value) -> Vec<String> {
+        let schema: Schema = serde_json::from_value(schema).unwrap();
+        validate(&schema, &instance)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn accepts_conforming_values() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "minLength": 1 },
+                "count": { "type": "integer", "minimum": 0, "maximum": 10 },
+                "tags": { "type": "array", "items": { "type": "string" } }
+            },
+            "required": ["name"]
+        });
+        assert!(violations(schema.clone(), json!({ "name": "a", "count": 3 })).is_empty());
+        assert!(violations(schema, json!({ "name": "a", "tags": ["x", "y"] })).is_empty());
+    }
+
+    #[test]
+    fn reports_every_violation_with_its_path() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "count": { "type": "integer", "maximum": 10 },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "nested": {
+                    "type": "object",
+                    "properties": { "a/b": { "type": "boolean" } }
+                }
+            },
+            "required": ["name"],
+            "additionalProperties": false
+        });
+        let instance = json!({
+            "count": 11,
+            "tags": ["x", 2],
+            "nested": { "a/b": "yes" },
+            "extra": null
+        });
+        assert_eq!(
+            violations(schema, instance),
+            [
+                "missing required property 'name'",
+                "/count: must be <= 10",
+                "/extra: is not an allowed property",
+                "/nested/a~1b: expected boolean, found string",
+                "/tags/1: expected string, found integer",
+            ]
+        );
+    }
+
+    #[test]
+    fn checks_scalar_keywords() {
+        assert_eq!(
+            violations(json!({ "type": "string" }), json!(1.5)),
+            ["expected string, found number"]
+        );
+        assert_eq!(
+            violations(json!({ "type": ["string", "null"] }), json!(true)),
+            ["expected string or null, found boolean"]
+        );
+        assert!(violations(json!({ "type": "integer" }), json!(2.0)).is_empty());
+        assert_eq!(
+            violations(json!({ "enum": ["a", "b"] }), json!("c")),
+            [r#"must be one of ["a","b"]"#]
+        );
+        assert!(violations(json!({ "enum": [1, 2] }), json!(2.0)).is_empty());
+        assert_eq!(violations(json!({ "const": 3 }), json!(4)), ["must be 3"]);
+        assert_eq!(
+            violations(json!({ "exclusiveMinimum": 0, "multipleOf": 5 }), json!(0)),
+            ["must be > 0"]
+        );
+        assert_eq!(
+            violations(json!({ "multipleOf": 5 }), json!(7)),
+            ["must be a multiple of 5"]
+        );
+        assert_eq!(
+            violations(json!({ "maxLength": 2 }), json!("héé")),
+            ["must be at most 2 characters long"]
+        );
+        assert_eq!(
+            violations(json!(false), json!(1)),
+            ["no value is allowed here"]
+        );
+        assert!(violations(json!(true), json!(1)).is_empty());
+    }
+
+    #[test]
+    fn checks_formats() {
+        let check = |format: &str, value: &str| {
+            violations(json!({ "type": "string", "format": format }), json!(value)).is_empty()
+        };
+        assert!(check("date-time", "2024-05-01T12:00:00Z"));
+        assert!(!check("date-time", "2024-05-01 12:00"));
+        assert!(check("date", "2024-02-29"));
+        assert!(!check("date", "2023-02-29"));
+        assert!(check("email", "a@example.com"));
+        assert!(!check("email", "a@example"));
+        assert!(check("uri", "https://example.com/x"));
+        assert!(!check("uri", "not a uri"));
+        assert!(check("uuid", "123e4567-e89b-12d3-a456-426614174000"));
+        assert!(!check("uuid", "123e4567"));
+        assert!(check("ipv4", "10.0.0.1"));
+        assert!(!check("ipv6", "10.0.0.1"));
+        assert!(!check("hostname", "-bad.example"));
+        assert!(check("unknown-format", "anything"));
+    }
+
+    #[test]
+    fn checks_arrays() {
+        let schema = json!({
+            "type": "array",
+            "prefixItems": [{ "type": "string" }],
+            "items": { "type": "integer" },
+            "minItems": 2,
+            "uniqueItems": true
+        });
+        assert!(violations(schema.clone(), json!(["a", 1, 2])).is_empty());
+        assert_eq!(
+            violations(schema.clone(), json!([1])),
+            [
+                "must contain at least 2 items",
+                "/0: expected string, found integer"
+            ]
+        );
+        assert_eq!(
+            violations(schema, json!(["a", 1, 1])),
+            ["/2: duplicates an earlier item"]
+        );
+    }
+
+    #[test]
+    fn follows_local_refs() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "child": { "$ref": "#/$defs/node" } },
+            "$defs": {
+                "node": {
+                    "type": "object",
+                    "properties": { "child": { "$ref": "#/$defs/node" }, "n": { "type": "integer" } }
+                }
+            }
+        });
+        assert_eq!(
+            violations(schema, json!({ "child": { "child": { "n": "x" } } })),
+            ["/child/child/n: expected integer, found string"]
+        );
+    }
+
+    #[test]
+    fn recursive_ref_without_progress_terminates() {
+        assert!(violations(json!({ "$ref": "#" }), json!(1)).is_empty());
+    }
+
+    #[test]
+    fn checks_combinators() {
+        let option = json!({ "anyOf": [{ "type": "null" }, { "type": "integer", "minimum": 1 }] });
+        assert!(violations(option.clone(), json!(null)).is_empty());
+        assert_eq!(violations(option.clone(), json!(0)), ["must be >= 1"]);
+        assert_eq!(
+            violations(
+                json!({ "anyOf": [{ "type": "string" }, { "type": "integer" }] }),
+                json!(true)
+            ),
+            ["does not match any of the allowed schemas"]
+        );
+        assert_eq!(
+            violations(
+                json!({ "oneOf": [{ "type": "integer" }, { "minimum": 0 }] }),
+                json!(1)
+            ),
+            ["matches 2 of the allowed schemas, expected exactly one"]
+        );
+        assert_eq!(
+            violations(
+                json!({ "allOf": [{ "minimum": 0 }, { "maximum": 5 }] }),
+                json!(9)
+            ),
+            ["must be <= 5"]
+        );
+        assert_eq!(
+            violations(json!({ "not": { "type": "string" } }), json!("x")),
+            ["matches a schema it must not match"]
+        );
+        let conditional = json!({
+            "if": { "properties": { "kind": { "const": "file" } } },
+            "then": { "required": ["path"] },
+            "else": { "required": ["url"] }
+        });
+        assert_eq!(
+            violations(conditional.clone(), json!({ "kind": "file" })),
+            ["missing required property 'path'"]
+        );
+        assert_eq!(
+            violations(conditional, json!({ "kind": "link" })),
+            ["missing required property 'url'"]
+        );
+    }
+
+    #[test]
+    fn additional_properties_schema_applies_to_unknown_keys() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "a": {} },
+            "additionalProperties": { "type": "integer" }
+        });
+        assert_eq!(
+            violations(schema, json!({ "a": "x", "b": "y" })),
+            ["/b: expected integer, found string"]
+        );
+    }
+}